@@ -44,6 +44,8 @@ def export_skin(mesh, arm, fname):
 
 def export_anim(arm, fname):
     with open(fname, "w") as file:
+        # Every second frame is exported and played at 20 keys per second
+        file.write("fps 40\n\n")
         i = 0
         while i < 40:
             i += 2
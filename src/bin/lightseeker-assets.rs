@@ -0,0 +1,481 @@
+use std::{collections::HashMap, env, fs, path::Path, process, rc::Rc};
+
+use lightseeker::{
+    geometry::Vector3,
+    objects::parsers::{
+        animation::Animation,
+        binary::AssetBundle,
+        bvh::BvhAsset,
+        gltf::GltfAsset,
+        material::MaterialLibrary,
+        ply::PlyParser,
+        shape::{IndexedMesh, ObjParser, ObjWriter},
+        skeleton::Skeleton,
+        skinning::{Skinning, SkinningOptions},
+        stl::StlParser,
+        validation::{AssetProblem, AssetValidator},
+        ParseError,
+    },
+};
+
+const USAGE: &str = "Usage:
+    lightseeker-assets stats <file>...
+    lightseeker-assets validate <file>...
+    lightseeker-assets convert <input> <output>
+
+Options:
+    --skeleton <file.skl>     skeleton for .obj and .anim inputs
+    --skin <file.skin>        skin weights for .obj inputs
+    --prune <weight>          drop lighter skin influences, the strongest one stays
+    --anim <name>=<file.anim> animation for .obj inputs, may be repeated
+    --pick <name>             animation written to .anim outputs with several to choose from
+
+Inputs: .obj .mtl .stl .ply .skl .skin .anim .bvh .gltf .glb .lsab
+Outputs: .lsab .obj .skl .skin .anim";
+
+struct Options {
+    files: Vec<String>,
+    skeleton: Option<String>,
+    skin: Option<String>,
+    skinning: SkinningOptions,
+    animations: Vec<(String, String)>,
+    pick: Option<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self {
+            files: vec![],
+            skeleton: None,
+            skin: None,
+            skinning: SkinningOptions::default(),
+            animations: vec![],
+            pick: None,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                "--skeleton" => options.skeleton = Some(value()?),
+                "--skin" => options.skin = Some(value()?),
+                "--pick" => options.pick = Some(value()?),
+                "--prune" => {
+                    let value = value()?;
+                    options.skinning.threshold = value
+                        .parse()
+                        .map_err(|_| format!("Expected a weight, got {}", value))?;
+                }
+                "--anim" => {
+                    let value = value()?;
+                    let (name, file) = value
+                        .split_once('=')
+                        .ok_or_else(|| format!("Expected <name>=<file.anim>, got {}", value))?;
+                    options
+                        .animations
+                        .push((name.to_string(), file.to_string()));
+                }
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ => options.files.push(arg.clone()),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.split_first() {
+        Some((command, rest)) => Options::parse(rest).and_then(|options| match command.as_str() {
+            "stats" => stats(&options),
+            "validate" => validate(&options),
+            "convert" => convert(&options),
+            _ => Err(format!("Unknown command {}\n\n{}", command, USAGE)),
+        }),
+        None => Err(USAGE.to_string()),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn stats(options: &Options) -> Result<(), String> {
+    if options.files.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    for file in options.files.iter() {
+        println!("{}", file);
+        if extension(file) == "mtl" {
+            let library = MaterialLibrary::try_parse(&read_text(file)?)
+                .map_err(|err| err.with_file(file).to_string())?;
+            println!("  materials: {}", library.materials.len());
+            for material in library.materials.iter() {
+                println!(
+                    "    {}{}",
+                    material.name,
+                    material
+                        .diffuse_map
+                        .as_ref()
+                        .map(|it| format!(" ({})", it))
+                        .unwrap_or_default()
+                );
+            }
+            continue;
+        }
+        let (bundle, problems) = load(file, options)?;
+        print_bundle(&bundle);
+        for problem in problems {
+            println!("  problem: {}", problem);
+        }
+    }
+    Ok(())
+}
+
+/// Parses and cross-checks every file, reports all failures, not only the first one.
+fn validate(options: &Options) -> Result<(), String> {
+    if options.files.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let mut failed = 0;
+    for file in options.files.iter() {
+        let result = if extension(file) == "mtl" {
+            read_text(file).and_then(|text| {
+                MaterialLibrary::try_parse(&text)
+                    .map(|_| vec![])
+                    .map_err(|err| err.with_file(file).to_string())
+            })
+        } else {
+            load(file, options).map(|(_, problems)| problems)
+        };
+        match result {
+            Ok(problems) if problems.is_empty() => println!("ok      {}", file),
+            Ok(problems) => {
+                for problem in problems {
+                    println!("failed  {}: {}", file, problem);
+                }
+                failed += 1;
+            }
+            Err(err) => {
+                println!("failed  {}", err);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        Err(format!(
+            "{} of {} files failed",
+            failed,
+            options.files.len()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn convert(options: &Options) -> Result<(), String> {
+    let (input, output) = match options.files.as_slice() {
+        [input, output] => (input, output),
+        _ => return Err(USAGE.to_string()),
+    };
+    let (bundle, problems) = load(input, options)?;
+    for problem in problems {
+        eprintln!("warning: {}: {}", input, problem);
+    }
+
+    match extension(output).as_str() {
+        "lsab" => write(output, bundle.write()),
+        "obj" => {
+            let triangles: Vec<_> = bundle.meshes.iter().flat_map(|it| it.triangles()).collect();
+            if triangles.is_empty() {
+                return Err(format!("{} has no meshes", input));
+            }
+            write(output, ObjWriter::write(&triangles).into_bytes())
+        }
+        "skin" => {
+            let triangles: Vec<_> = bundle.meshes.iter().flat_map(|it| it.triangles()).collect();
+            match &bundle.skinning {
+                Some(skinning) => write(output, skinning.write().into_bytes()),
+                // Skin weights baked into the vertices of a glTF or bundle mesh
+                None if !triangles.is_empty() => {
+                    let (_, skinning) = ObjWriter::write_with_skin(&triangles);
+                    write(output, skinning.write().into_bytes())
+                }
+                None => Err(format!("{} has no skin weights", input)),
+            }
+        }
+        "skl" => match &bundle.skeleton {
+            Some(skeleton) => write(output, skeleton.write().into_bytes()),
+            None => Err(format!("{} has no skeleton", input)),
+        },
+        "anim" => write(
+            output,
+            pick_animation(&bundle, input, options)?
+                .write()
+                .into_bytes(),
+        ),
+        other => Err(format!("Cannot convert to .{}", other)),
+    }
+}
+
+/// The animation named by `--pick`, or the only one of the bundle.
+fn pick_animation<'a>(
+    bundle: &'a AssetBundle,
+    input: &str,
+    options: &Options,
+) -> Result<&'a Animation, String> {
+    let mut names: Vec<_> = bundle.animation_names.keys().cloned().collect();
+    names.sort_by_key(|it| bundle.animation_names[it]);
+    match (&options.pick, bundle.animations.as_slice()) {
+        (Some(name), _) => bundle.animation(name).map(|it| it.as_ref()).ok_or_else(|| {
+            format!(
+                "{} has no animation {}, it has: {}",
+                input,
+                name,
+                names.join(", ")
+            )
+        }),
+        (None, []) => Err(format!("{} has no animations", input)),
+        (None, [animation]) => Ok(animation),
+        (None, animations) => Err(format!(
+            "{} has {} animations, pick one with --pick <name>: {}",
+            input,
+            animations.len(),
+            names.join(", ")
+        )),
+    }
+}
+
+/// The bundle and the problems found when checking it against the other files.
+fn load(file: &str, options: &Options) -> Result<(AssetBundle, Vec<AssetProblem>), String> {
+    let with_file = |err: ParseError| err.with_file(file).to_string();
+    let skeleton = options
+        .skeleton
+        .as_ref()
+        .map(|path| {
+            let text = read_text(path)?;
+            Skeleton::try_from_file(&text)
+                .map(Rc::new)
+                .map_err(|err| err.with_file(path).to_string())
+        })
+        .transpose()?;
+
+    let mut problems = vec![];
+    let bundle = match extension(file).as_str() {
+        "obj" => {
+            let skin = options.skin.as_deref().map(read_text).transpose()?;
+            let skinning = skin
+                .as_deref()
+                .map(|it| Skinning::try_parse_with_options(it, &options.skinning))
+                .transpose()
+                .map_err(|err| err.with_file(options.skin.as_deref().unwrap()).to_string())?;
+            let mesh = ObjParser::try_parse_mesh(&read_text(file)?, skinning.as_ref())
+                .map_err(with_file)?;
+            if let Some(skinning) = &skinning {
+                problems = AssetValidator::new()
+                    .with_mesh(&mesh)
+                    .with_skinning(skinning)
+                    .validate();
+            }
+            let mut bundle = AssetBundle::new().with_mesh(IndexedMesh::from_mesh(&mesh));
+            if let Some(skinning) = skinning {
+                bundle = bundle.with_skinning(skinning);
+            }
+            if let Some(skeleton) = skeleton {
+                for (name, path) in options.animations.iter() {
+                    let animation = Animation::try_parse(&read_text(path)?, &skeleton)
+                        .map_err(|err| err.with_file(path).to_string())?;
+                    bundle = bundle.with_animation(name, Rc::new(animation));
+                }
+                bundle = bundle.with_skeleton(skeleton);
+            } else if !options.animations.is_empty() {
+                return Err("Animations need --skeleton".to_string());
+            }
+            bundle
+        }
+        "stl" => AssetBundle::new().with_mesh(IndexedMesh::from_mesh(
+            &StlParser::try_parse_mesh(&read(file)?).map_err(with_file)?,
+        )),
+        "ply" => AssetBundle::new().with_mesh(IndexedMesh::from_mesh(
+            &PlyParser::try_parse_mesh(&read(file)?).map_err(with_file)?,
+        )),
+        "skl" => AssetBundle::new().with_skeleton(Rc::new(
+            Skeleton::try_from_file(&read_text(file)?).map_err(with_file)?,
+        )),
+        "skin" => AssetBundle::new().with_skinning(
+            Skinning::try_parse_with_options(&read_text(file)?, &options.skinning)
+                .map_err(with_file)?,
+        ),
+        "anim" => {
+            // Frames do not depend on the skeleton, it only has to be there for the bundle
+            let skeleton = skeleton.unwrap_or_else(|| {
+                Rc::new(Skeleton {
+                    bones: vec![],
+                    names: HashMap::new(),
+                })
+            });
+            let animation =
+                Animation::try_parse(&read_text(file)?, &skeleton).map_err(with_file)?;
+            AssetBundle::new()
+                .with_animation(&stem(file), Rc::new(animation))
+                .with_skeleton(skeleton)
+        }
+        "bvh" => {
+            let asset = BvhAsset::try_parse(&read_text(file)?, 1.0).map_err(with_file)?;
+            AssetBundle::new()
+                .with_animation(&stem(file), asset.animation)
+                .with_skeleton(asset.skeleton)
+        }
+        "gltf" => {
+            AssetBundle::from_gltf(&GltfAsset::try_parse(&read_text(file)?).map_err(with_file)?)
+        }
+        "glb" => {
+            AssetBundle::from_gltf(&GltfAsset::try_parse_glb(&read(file)?).map_err(with_file)?)
+        }
+        "lsab" => AssetBundle::try_parse(&read(file)?).map_err(with_file)?,
+        other => return Err(format!("{}: unsupported file type .{}", file, other)),
+    };
+    // Without --skeleton an animation only has a placeholder to be checked against
+    if extension(file) != "anim" || options.skeleton.is_some() {
+        problems.extend(bundle.validate());
+    }
+    Ok((bundle, problems))
+}
+
+fn print_bundle(bundle: &AssetBundle) {
+    for mesh in bundle.meshes.iter() {
+        print_mesh(mesh);
+    }
+    if let Some(skeleton) = &bundle.skeleton {
+        if !skeleton.bones.is_empty() {
+            println!("  skeleton: {} bones", skeleton.bones.len());
+            print_bones(skeleton, -1, 2);
+        }
+    }
+    if let Some(skinning) = &bundle.skinning {
+        let influences = skinning
+            .vertices
+            .iter()
+            .map(|vertex| (0..4).filter(|i| vertex.weights.get(*i) != 0.0).count())
+            .max()
+            .unwrap_or(0);
+        println!(
+            "  skinning: {} vertices, up to {} bones per vertex",
+            skinning.vertices.len(),
+            influences
+        );
+        let pruned = skinning
+            .vertices
+            .iter()
+            .filter(|it| it.discarded > 0.0)
+            .count();
+        if pruned > 0 {
+            println!(
+                "    {} vertices lost influences, up to {} of their weight",
+                pruned,
+                skinning.max_discarded()
+            );
+        }
+    }
+    let mut names: Vec<_> = bundle.animation_names.iter().collect();
+    names.sort_by_key(|(_, idx)| **idx);
+    for (name, idx) in names {
+        let animation = &bundle.animations[*idx];
+        println!(
+            "  animation {}: {} keyframes over {:.2}s at {} fps, {} bones",
+            name,
+            animation.frames.len(),
+            animation.duration(),
+            animation.frame_rate,
+            animation.frames.first().map_or(0, |it| it.transforms.len())
+        );
+        for clip in animation.clips.iter() {
+            println!(
+                "    clip {}: frames {} to {}{}",
+                clip.name,
+                clip.start,
+                clip.end,
+                if clip.looping { ", looping" } else { "" }
+            );
+        }
+    }
+}
+
+fn print_mesh(mesh: &IndexedMesh) {
+    println!(
+        "  mesh {}: {} triangles, {} vertices",
+        if mesh.name.is_empty() {
+            "-"
+        } else {
+            &mesh.name
+        },
+        mesh.indices.len() / 3,
+        mesh.vertices.len()
+    );
+    if let Some((min, max)) = bounds(mesh) {
+        println!("    bounds: {} .. {}", format_point(min), format_point(max));
+    }
+    for submesh in mesh.submeshes.iter() {
+        println!(
+            "    {}: {} triangles",
+            submesh.material.as_deref().unwrap_or("no material"),
+            submesh.count / 3
+        );
+    }
+}
+
+fn print_bones(skeleton: &Skeleton, parent: isize, depth: usize) {
+    for (idx, bone) in skeleton.bones.iter().enumerate() {
+        if bone.parent == parent {
+            println!("{:width$}{}", "", bone.name, width = depth * 2);
+            print_bones(skeleton, idx as isize, depth + 1);
+        }
+    }
+}
+
+fn bounds(mesh: &IndexedMesh) -> Option<(Vector3, Vector3)> {
+    let first = mesh.vertices.first()?.point;
+    Some(mesh.vertices.iter().fold((first, first), |(min, max), it| {
+        let p = it.point;
+        (
+            Vector3::from_xyz(min.x().min(p.x()), min.y().min(p.y()), min.z().min(p.z())),
+            Vector3::from_xyz(max.x().max(p.x()), max.y().max(p.y()), max.z().max(p.z())),
+        )
+    }))
+}
+
+fn format_point(point: Vector3) -> String {
+    format!("({:.3}, {:.3}, {:.3})", point.x(), point.y(), point.z())
+}
+
+fn extension(file: &str) -> String {
+    Path::new(file)
+        .extension()
+        .and_then(|it| it.to_str())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+fn stem(file: &str) -> String {
+    Path::new(file)
+        .file_stem()
+        .and_then(|it| it.to_str())
+        .unwrap_or(file)
+        .to_string()
+}
+
+fn read(file: &str) -> Result<Vec<u8>, String> {
+    fs::read(file).map_err(|err| format!("{}: {}", file, err))
+}
+
+fn read_text(file: &str) -> Result<String, String> {
+    fs::read_to_string(file).map_err(|err| format!("{}: {}", file, err))
+}
+
+fn write(file: &str, data: Vec<u8>) -> Result<(), String> {
+    fs::write(file, data).map_err(|err| format!("{}: {}", file, err))
+}
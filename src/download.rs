@@ -5,11 +5,12 @@ use futures::{
     future::{join, join_all},
 };
 use gloo::net::http::Request;
+use js_sys::Uint8Array;
 use wasm_bindgen::{prelude::Closure, JsCast};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{HtmlImageElement, Url, WebGl2RenderingContext as Gl};
 
-use crate::objects::texture::Texture;
+use crate::objects::{parsers::ParseResult, texture::Texture};
 
 #[derive(Clone)]
 pub struct ResourceManager(Rc<RefCell<ResourceBatch>>);
@@ -22,6 +23,7 @@ impl ResourceManager {
     pub async fn download(requests: Vec<ResourceRequest>, gl: Gl) -> ResourceBatch {
         let mut textures = vec![];
         let mut texts = vec![];
+        let mut binaries = vec![];
         for req in requests.iter() {
             match req {
                 ResourceRequest::Image(req) => {
@@ -37,14 +39,33 @@ impl ResourceManager {
                         (req.name.clone(), Rc::new(download_text(&req.path).await))
                     });
                 }
+                ResourceRequest::Binary(req) => {
+                    binaries.push(async {
+                        (req.name.clone(), Rc::new(download_binary(&req.path).await))
+                    });
+                }
             };
         }
 
-        let (textures, texts) = join(join_all(textures), join_all(texts)).await;
+        let ((textures, texts), binaries) = join(
+            join(join_all(textures), join_all(texts)),
+            join_all(binaries),
+        )
+        .await;
+
+        let paths = requests
+            .iter()
+            .filter_map(|req| match req {
+                ResourceRequest::Text(req) => Some((req.name.clone(), req.path.clone())),
+                _ => None,
+            })
+            .collect();
 
         ResourceBatch {
             textures: textures.into_iter().collect(),
             texts: texts.into_iter().collect(),
+            binaries: binaries.into_iter().collect(),
+            paths,
         }
     }
 
@@ -56,15 +77,44 @@ impl ResourceManager {
         for (name, text) in other.texts {
             inner.texts.insert(name, text);
         }
+        for (name, binary) in other.binaries {
+            inner.binaries.insert(name, binary);
+        }
+        for (name, path) in other.paths {
+            inner.paths.insert(name, path);
+        }
     }
 
     pub fn get_texture(&self, name: &str) -> Rc<Texture> {
         self.0.borrow().textures[name].clone()
     }
 
+    pub fn try_get_texture(&self, name: &str) -> Option<Rc<Texture>> {
+        self.0.borrow().textures.get(name).cloned()
+    }
+
     pub fn get_text(&self, name: &str) -> Rc<String> {
         self.0.borrow().texts[name].clone()
     }
+
+    pub fn get_binary(&self, name: &str) -> Rc<Vec<u8>> {
+        self.0.borrow().binaries[name].clone()
+    }
+
+    /// Parses a downloaded text, errors are logged with the path of the file and give `None`.
+    pub fn parse_text<T>(
+        &self,
+        name: &str,
+        parse: impl FnOnce(&str) -> ParseResult<T>,
+    ) -> Option<T> {
+        let text = self.get_text(name);
+        parse(&text)
+            .map_err(|err| {
+                let path = self.0.borrow().paths.get(name).cloned();
+                log::error!("{}", err.with_file(path.as_deref().unwrap_or(name)));
+            })
+            .ok()
+    }
 }
 
 impl Default for ResourceManager {
@@ -76,6 +126,9 @@ impl Default for ResourceManager {
 pub struct ResourceBatch {
     textures: HashMap<String, Rc<Texture>>,
     texts: HashMap<String, Rc<String>>,
+    binaries: HashMap<String, Rc<Vec<u8>>>,
+    /// Paths the texts were downloaded from, for error messages
+    paths: HashMap<String, String>,
 }
 
 impl ResourceBatch {
@@ -83,6 +136,8 @@ impl ResourceBatch {
         Self {
             textures: HashMap::new(),
             texts: HashMap::new(),
+            binaries: HashMap::new(),
+            paths: HashMap::new(),
         }
     }
 }
@@ -90,6 +145,7 @@ impl ResourceBatch {
 pub enum ResourceRequest {
     Image(RequestedEntity),
     Text(RequestedEntity),
+    Binary(RequestedEntity),
 }
 
 impl ResourceRequest {
@@ -100,6 +156,10 @@ impl ResourceRequest {
     pub fn text(path: &str, name: &str) -> Self {
         Self::Text(RequestedEntity::new(path, name))
     }
+
+    pub fn binary(path: &str, name: &str) -> Self {
+        Self::Binary(RequestedEntity::new(path, name))
+    }
 }
 
 pub struct RequestedEntity {
@@ -126,6 +186,14 @@ pub async fn download_text(path: &str) -> String {
     text
 }
 
+pub async fn download_binary(path: &str) -> Vec<u8> {
+    let resp = Request::get(path).send().await.unwrap();
+    let buffer = JsFuture::from(resp.as_raw().array_buffer().unwrap())
+        .await
+        .unwrap();
+    Uint8Array::new(&buffer).to_vec()
+}
+
 pub async fn download_image(path: &str) -> HtmlImageElement {
     let resp = Request::get(path).send().await.unwrap();
     let blob = JsFuture::from(resp.as_raw().blob().unwrap()).await.unwrap();
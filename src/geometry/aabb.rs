@@ -16,6 +16,12 @@ impl Aabb {
         }
     }
 
+    /// Lowest and highest corners.
+    pub fn bounds(&self) -> (Vector3, Vector3) {
+        let center = self.center.position();
+        (center - self.half_size, center + self.half_size)
+    }
+
     pub fn frame_matrix(&self) -> Matrix {
         let mut tr = RawTransform::new();
         tr.scale = self.half_size;
@@ -0,0 +1,135 @@
+use super::Vector3;
+
+/// Sweep and prune along the x axis, finds the pairs of boxes that may collide.
+///
+/// Keeps the boxes sorted between updates. Bodies move little from one frame to the next,
+/// so the insertion sort restoring the order is close to linear.
+#[derive(Default)]
+pub struct SweepAndPrune {
+    bounds: Vec<(Vector3, Vector3)>,
+    /// Box indices by their lowest x
+    order: Vec<usize>,
+}
+
+impl SweepAndPrune {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the boxes, given as lowest and highest corners. Boxes keep their indices,
+    /// new ones are appended after the existing ones.
+    pub fn update(&mut self, bounds: Vec<(Vector3, Vector3)>) {
+        self.order.retain(|it| *it < bounds.len());
+        self.order.extend(self.bounds.len()..bounds.len());
+        self.bounds = bounds;
+
+        for i in 1..self.order.len() {
+            let idx = self.order[i];
+            let x = self.min_x(idx);
+            let mut j = i;
+            while j > 0 && self.min_x(self.order[j - 1]) > x {
+                self.order[j] = self.order[j - 1];
+                j -= 1;
+            }
+            self.order[j] = idx;
+        }
+    }
+
+    /// Pairs `(i, j)` with `i < j` of boxes that overlap or touch, sorted. Only the boxes
+    /// of the last `update` count, moving the bodies afterwards does not change the pairs.
+    pub fn pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        for (k, &a) in self.order.iter().enumerate() {
+            let (a_min, a_max) = self.bounds[a];
+            for &b in self.order[k + 1..].iter() {
+                let (b_min, b_max) = self.bounds[b];
+                if b_min.x() > a_max.x() {
+                    // Everything further starts even later
+                    break;
+                }
+                let overlaps =
+                    (1..3).all(|i| a_min.get(i) <= b_max.get(i) && b_min.get(i) <= a_max.get(i));
+                if overlaps {
+                    pairs.push((a.min(b), a.max(b)));
+                }
+            }
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+
+    fn min_x(&self, idx: usize) -> f32 {
+        self.bounds[idx].0.x()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_force(bounds: &[(Vector3, Vector3)]) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        for (i, (a_min, a_max)) in bounds.iter().enumerate() {
+            for (j, (b_min, b_max)) in bounds.iter().enumerate().skip(i + 1) {
+                if (0..3).all(|k| a_min.get(k) <= b_max.get(k) && b_min.get(k) <= a_max.get(k)) {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn pairs_match_brute_force() {
+        // Deterministic numbers in `[0, 1)`
+        let mut state = 11u64;
+        let mut random = move || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 40) as f32 / (1u64 << 24) as f32
+        };
+
+        let mut sap = SweepAndPrune::new();
+        let mut centers: Vec<Vector3> = vec![];
+        for count in [40, 60, 60, 25, 80] {
+            // Boxes move a little between updates, some disappear and new ones appear
+            centers.truncate(count);
+            for center in centers.iter_mut() {
+                *center += Vector3::from_xyz(random() - 0.5, random() - 0.5, random() - 0.5);
+            }
+            while centers.len() < count {
+                centers.push(Vector3::from_xyz(random(), random(), random()) * 10.0);
+            }
+            let bounds: Vec<_> = centers
+                .iter()
+                .map(|&center| {
+                    let half = Vector3::from_xyz(random(), random(), random()) * 1.5;
+                    (center - half, center + half)
+                })
+                .collect();
+
+            sap.update(bounds.clone());
+            let expected = brute_force(&bounds);
+            assert!(!expected.is_empty());
+            assert_eq!(sap.pairs(), expected);
+        }
+    }
+
+    #[test]
+    fn touching_boxes_are_paired() {
+        let mut sap = SweepAndPrune::new();
+        sap.update(vec![
+            (Vector3::zero(), Vector3::repeat(1.0)),
+            (
+                Vector3::from_xyz(1.0, 0.0, 0.0),
+                Vector3::from_xyz(2.0, 1.0, 1.0),
+            ),
+            (
+                Vector3::from_xyz(2.5, 0.0, 0.0),
+                Vector3::from_xyz(3.0, 1.0, 1.0),
+            ),
+        ]);
+        assert_eq!(sap.pairs(), vec![(0, 1)]);
+    }
+}
@@ -0,0 +1,168 @@
+use super::{
+    aabb::Aabb,
+    obb::{BoxFrame, Obb},
+    raycast::Ray,
+    sphere::{balls_mtv, cast_ray_ball, Sphere},
+    transform::RawTransform,
+    Matrix, Transform, Vector3,
+};
+
+/// Upright capsule around the position of its `center` transform.
+///
+/// Like `Aabb` it ignores the rotation of the transform, so characters do not tip over.
+/// `height` is the whole height including the rounded ends.
+#[derive(Clone)]
+pub struct Capsule {
+    pub center: Transform,
+    pub radius: f32,
+    pub height: f32,
+}
+
+impl Capsule {
+    pub fn new(center: Transform, radius: f32, height: f32) -> Self {
+        Self {
+            center,
+            radius,
+            height,
+        }
+    }
+
+    /// Bottom and top of the segment the capsule is rounded around.
+    pub fn segment(&self) -> (Vector3, Vector3) {
+        let center = self.center.position();
+        let half = Vector3::from_xyz(0.0, (self.height * 0.5 - self.radius).max(0.0), 0.0);
+        (center - half, center + half)
+    }
+
+    /// Corners of the box around the capsule.
+    pub fn bounds(&self) -> (Vector3, Vector3) {
+        let (bottom, top) = self.segment();
+        let radius = Vector3::repeat(self.radius);
+        (bottom - radius, top + radius)
+    }
+
+    /// Frame of the bounding box, for gizmos.
+    pub fn frame_matrix(&self) -> Matrix {
+        let mut tr = RawTransform::new();
+        tr.scale = Vector3::from_xyz(
+            self.radius,
+            (self.height * 0.5).max(self.radius),
+            self.radius,
+        );
+        tr.position = self.center.position();
+        tr.reverse_matrix()
+    }
+
+    /// Shortest translation that moves `self` out of `other`, `None` if they do not overlap.
+    pub fn find_mtv(&self, other: &Self) -> Option<Vector3> {
+        let (a_bottom, a_top) = self.segment();
+        let (b_bottom, b_top) = other.segment();
+        let low = a_bottom.y().max(b_bottom.y());
+        let high = a_top.y().min(b_top.y());
+        let (a, b) = if low <= high {
+            // The segments share heights, the closest points are side by side
+            let y = (low + high) * 0.5;
+            (
+                Vector3::from_xyz(a_bottom.x(), y, a_bottom.z()),
+                Vector3::from_xyz(b_bottom.x(), y, b_bottom.z()),
+            )
+        } else if a_top.y() < b_bottom.y() {
+            (a_top, b_bottom)
+        } else {
+            (a_bottom, b_top)
+        };
+        balls_mtv(a, self.radius, b, other.radius)
+    }
+
+    pub fn find_mtv_sphere(&self, other: &Sphere) -> Option<Vector3> {
+        let center = other.center.position();
+        balls_mtv(
+            self.closest_point(center),
+            self.radius,
+            center,
+            other.radius,
+        )
+    }
+
+    /// Like `Aabb::find_mtv`, the capsule steps onto boxes lower than `slope_height`.
+    pub fn find_mtv_aabb(&self, other: &Aabb, slope_height: Option<f32>) -> Option<Vector3> {
+        let vector = self.find_mtv_box(&BoxFrame::from_aabb(other))?;
+
+        if let Some(slope_height) = slope_height {
+            let (bottom, top) = self.segment();
+            let other_center = other.center.position();
+
+            let slf_bot = bottom.y() - self.radius;
+            let oth_top = other_center.y() + other.half_size.y();
+            if slf_bot < oth_top && slf_bot + slope_height > oth_top {
+                return Some(Vector3::from_xyz(0.0, oth_top - slf_bot, 0.0));
+            }
+
+            let slf_top = top.y() + self.radius;
+            let oth_bot = other_center.y() - other.half_size.y();
+            if oth_bot < slf_top && oth_bot + slope_height > slf_top {
+                return Some(Vector3::from_xyz(0.0, oth_bot - slf_top, 0.0));
+            }
+        }
+
+        Some(vector)
+    }
+
+    pub fn find_mtv_obb(&self, other: &Obb) -> Option<Vector3> {
+        self.find_mtv_box(&other.frame())
+    }
+
+    /// Distance along the ray and the point where it enters the capsule.
+    pub fn cast_ray(&self, ray: &Ray) -> Option<(f32, Vector3)> {
+        let (bottom, top) = self.segment();
+        if (ray.point - self.closest_point(ray.point)).length() <= self.radius {
+            return Some((0.0, ray.point));
+        }
+
+        let mut hit = None;
+        // Side of the cylinder between the rounded ends
+        let offset = ray.point - bottom;
+        let a = ray.direction.x().powi(2) + ray.direction.z().powi(2);
+        let b = offset.x() * ray.direction.x() + offset.z() * ray.direction.z();
+        let c = offset.x().powi(2) + offset.z().powi(2) - self.radius * self.radius;
+        let discriminant = b * b - a * c;
+        if a > 0.0 && discriminant >= 0.0 {
+            let dist = (-b - discriminant.sqrt()) / a;
+            let point = ray.point + ray.direction * dist;
+            if dist >= 0.0 && (bottom.y()..=top.y()).contains(&point.y()) {
+                hit = Some((dist, point));
+            }
+        }
+        for end in [bottom, top] {
+            if let Some((dist, point)) = cast_ray_ball(ray, end, self.radius) {
+                if hit.is_none_or(|(min, _)| dist < min) {
+                    hit = Some((dist, point));
+                }
+            }
+        }
+        hit
+    }
+
+    fn closest_point(&self, point: Vector3) -> Vector3 {
+        let (bottom, top) = self.segment();
+        Vector3::from_xyz(bottom.x(), point.y().clamp(bottom.y(), top.y()), bottom.z())
+    }
+
+    /// The signed distance to a box is convex along the segment, so a ternary search
+    /// finds the deepest point, which then collides as a ball.
+    fn find_mtv_box(&self, frame: &BoxFrame) -> Option<Vector3> {
+        let (bottom, top) = self.segment();
+        let at = |t: f32| bottom + (top - bottom) * t;
+        let (mut low, mut high) = (0.0, 1.0);
+        for _ in 0..32 {
+            let a = low + (high - low) / 3.0;
+            let b = high - (high - low) / 3.0;
+            if frame.signed_distance(at(a)) < frame.signed_distance(at(b)) {
+                high = b;
+            } else {
+                low = a;
+            }
+        }
+        frame.ball_mtv(at((low + high) * 0.5), self.radius)
+    }
+}
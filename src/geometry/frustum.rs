@@ -0,0 +1,62 @@
+use super::{Matrix, Vector3};
+
+/// Volume a projection matrix maps into the clip cube, e.g. what a camera or a shadow map sees.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far planes as unit normals pointing inside and offsets
+    planes: [(Vector3, f32); 6],
+}
+
+impl Frustum {
+    /// Planes of a `projection * view` matrix, like `Camera::matrix`.
+    pub fn from_matrix(matrix: &Matrix) -> Self {
+        // A point is inside when `-w <= x, y, z <= w` in clip space
+        let plane = |axis: usize, sign: f32| {
+            let coef = |j: usize| matrix.get(3, j) + matrix.get(axis, j) * sign;
+            let normal = Vector3::from_xyz(coef(0), coef(1), coef(2));
+            let length = normal.length();
+            (normal * (1.0 / length), coef(3) / length)
+        };
+
+        Self {
+            planes: [
+                plane(0, 1.0),
+                plane(0, -1.0),
+                plane(1, 1.0),
+                plane(1, -1.0),
+                plane(2, 1.0),
+                plane(2, -1.0),
+            ],
+        }
+    }
+
+    pub fn contains_point(&self, point: Vector3) -> bool {
+        self.planes
+            .iter()
+            .all(|it| Self::distance(it, point) >= 0.0)
+    }
+
+    pub fn intersects_sphere(&self, center: Vector3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|it| Self::distance(it, center) >= -radius)
+    }
+
+    /// Conservative, boxes near a frustum edge may pass while being outside.
+    pub fn intersects_box(&self, min: Vector3, max: Vector3) -> bool {
+        self.planes.iter().all(|plane| {
+            // Corner furthest along the plane normal
+            let normal = plane.0;
+            let corner = Vector3::from_xyz(
+                if normal.x() >= 0.0 { max.x() } else { min.x() },
+                if normal.y() >= 0.0 { max.y() } else { min.y() },
+                if normal.z() >= 0.0 { max.z() } else { min.z() },
+            );
+            Self::distance(plane, corner) >= 0.0
+        })
+    }
+
+    fn distance(plane: &(Vector3, f32), point: Vector3) -> f32 {
+        plane.0.dot(point) + plane.1
+    }
+}
@@ -1,7 +1,14 @@
 use std::fmt::Debug;
 use std::ops::{Deref, Mul};
 
-use super::vector::{Vector3, Vector4};
+use super::{
+    transform::RawTransform,
+    vector::{Vector3, Vector4},
+    Quaternion,
+};
+
+/// Determinants closer to zero than this make `Matrix::inverse` give up.
+const SINGULAR_DETERMINANT: f32 = 1e-12;
 
 #[derive(Clone, Copy)]
 pub struct Matrix {
@@ -124,6 +131,115 @@ impl Matrix {
         }
         matrix
     }
+
+    pub fn determinant(&self) -> f32 {
+        let (s, c) = self.minors();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    /// `None` for singular matrices, e.g. ones that scale an axis to zero.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det.abs() < SINGULAR_DETERMINANT || !det.is_finite() {
+            return None;
+        }
+
+        let m = |i: usize, j: usize| self.get(i, j);
+        let (s, c) = self.minors();
+        let adjugate: Self = [
+            [
+                m(1, 1) * c[5] - m(1, 2) * c[4] + m(1, 3) * c[3],
+                -m(0, 1) * c[5] + m(0, 2) * c[4] - m(0, 3) * c[3],
+                m(3, 1) * s[5] - m(3, 2) * s[4] + m(3, 3) * s[3],
+                -m(2, 1) * s[5] + m(2, 2) * s[4] - m(2, 3) * s[3],
+            ],
+            [
+                -m(1, 0) * c[5] + m(1, 2) * c[2] - m(1, 3) * c[1],
+                m(0, 0) * c[5] - m(0, 2) * c[2] + m(0, 3) * c[1],
+                -m(3, 0) * s[5] + m(3, 2) * s[2] - m(3, 3) * s[1],
+                m(2, 0) * s[5] - m(2, 2) * s[2] + m(2, 3) * s[1],
+            ],
+            [
+                m(1, 0) * c[4] - m(1, 1) * c[2] + m(1, 3) * c[0],
+                -m(0, 0) * c[4] + m(0, 1) * c[2] - m(0, 3) * c[0],
+                m(3, 0) * s[4] - m(3, 1) * s[2] + m(3, 3) * s[0],
+                -m(2, 0) * s[4] + m(2, 1) * s[2] - m(2, 3) * s[0],
+            ],
+            [
+                -m(1, 0) * c[3] + m(1, 1) * c[1] - m(1, 2) * c[0],
+                m(0, 0) * c[3] - m(0, 1) * c[1] + m(0, 2) * c[0],
+                -m(3, 0) * s[3] + m(3, 1) * s[1] - m(3, 2) * s[0],
+                m(2, 0) * s[3] - m(2, 1) * s[1] + m(2, 2) * s[0],
+            ],
+        ]
+        .into();
+
+        let mut inverse = adjugate;
+        for value in inverse.matrix.iter_mut() {
+            *value /= det;
+        }
+        Some(inverse)
+    }
+
+    /// 2x2 determinants of the top two rows and of the bottom two rows,
+    /// shared by the determinant and the inverse.
+    fn minors(&self) -> ([f32; 6], [f32; 6]) {
+        let m = |i: usize, j: usize| self.get(i, j);
+        let s = [
+            m(0, 0) * m(1, 1) - m(1, 0) * m(0, 1),
+            m(0, 0) * m(1, 2) - m(1, 0) * m(0, 2),
+            m(0, 0) * m(1, 3) - m(1, 0) * m(0, 3),
+            m(0, 1) * m(1, 2) - m(1, 1) * m(0, 2),
+            m(0, 1) * m(1, 3) - m(1, 1) * m(0, 3),
+            m(0, 2) * m(1, 3) - m(1, 2) * m(0, 3),
+        ];
+        let c = [
+            m(2, 0) * m(3, 1) - m(3, 0) * m(2, 1),
+            m(2, 0) * m(3, 2) - m(3, 0) * m(2, 2),
+            m(2, 0) * m(3, 3) - m(3, 0) * m(2, 3),
+            m(2, 1) * m(3, 2) - m(3, 1) * m(2, 2),
+            m(2, 1) * m(3, 3) - m(3, 1) * m(2, 3),
+            m(2, 2) * m(3, 3) - m(3, 2) * m(2, 3),
+        ];
+        (s, c)
+    }
+
+    /// Splits an affine matrix without shear into the transform that builds it.
+    ///
+    /// Mirroring shows up as a negative x scale. A single axis scaled to zero is
+    /// rebuilt from the other two, so the rotation survives it.
+    pub fn decompose(&self) -> RawTransform {
+        let column = |j: usize| Vector3::from_xyz(self.get(0, j), self.get(1, j), self.get(2, j));
+        let mut scale =
+            Vector3::from_xyz(column(0).length(), column(1).length(), column(2).length());
+        if self.determinant() < 0.0 {
+            scale.set(0, -scale.x());
+        }
+
+        let mut axes = [column(0), column(1), column(2)];
+        for (j, axis) in axes.iter_mut().enumerate() {
+            if scale.get(j) != 0.0 {
+                *axis *= 1.0 / scale.get(j);
+            }
+        }
+        let flat: Vec<usize> = (0..3).filter(|&j| scale.get(j) == 0.0).collect();
+        if let [j] = flat[..] {
+            axes[j] = axes[(j + 1) % 3].cross(axes[(j + 2) % 3]);
+        }
+
+        let mut rotation = [[0.0; 3]; 3];
+        for (j, axis) in axes.iter().enumerate() {
+            for (i, row) in rotation.iter_mut().enumerate() {
+                row[j] = axis.get(i);
+            }
+        }
+
+        let mut transform = RawTransform::new();
+        transform.position = column(3);
+        transform.rotation = Quaternion::from_rotation(&rotation);
+        transform.scale = scale;
+        transform
+    }
 }
 
 impl Mul<Matrix> for Matrix {
@@ -201,3 +317,117 @@ impl From<[[f32; Matrix::SIZE]; Matrix::SIZE]> for Matrix {
         matrix
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Matrix, b: &Matrix) {
+        for (x, y) in a.matrix.iter().zip(b.matrix.iter()) {
+            assert!((x - y).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn transforms() -> Vec<RawTransform> {
+        let axis = Vector3::from_xyz(1.0, -2.0, 0.5);
+        [
+            (
+                Vector3::zero(),
+                Quaternion::identity(),
+                Vector3::repeat(1.0),
+            ),
+            (
+                Vector3::from_xyz(1.0, 2.0, 3.0),
+                Quaternion::identity(),
+                Vector3::repeat(1.0),
+            ),
+            (
+                Vector3::from_xyz(-4.0, 0.5, 2.0),
+                Quaternion::from_axis_angle(axis, 0.7),
+                Vector3::from_xyz(2.0, 0.5, 3.0),
+            ),
+            (
+                Vector3::from_xyz(0.0, -1.0, 7.0),
+                Quaternion::from_axis_angle(axis, -2.5),
+                Vector3::from_xyz(-1.5, 1.0, 0.25),
+            ),
+        ]
+        .into_iter()
+        .map(|(position, rotation, scale)| RawTransform {
+            position,
+            rotation,
+            scale,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn inverse_matches_reverse_matrix() {
+        for transform in transforms() {
+            let matrix = transform.matrix();
+            let scale = transform.scale;
+            let determinant = matrix.determinant();
+            assert!((determinant - scale.x() * scale.y() * scale.z()).abs() < 1e-5);
+
+            let inverse = matrix.inverse().unwrap();
+            assert_close(&inverse, &transform.reverse_matrix());
+            assert_close(&(matrix * inverse), &Matrix::ident());
+        }
+    }
+
+    #[test]
+    fn decompose_rebuilds_the_transform() {
+        for transform in transforms() {
+            let decomposed = transform.matrix().decompose();
+            assert_close(&decomposed.matrix(), &transform.matrix());
+            assert_eq!(decomposed.position, transform.position);
+        }
+    }
+
+    #[test]
+    fn mirrored_matrices_decompose_with_negative_x_scale() {
+        let transform = RawTransform {
+            position: Vector3::from_xyz(1.0, 0.0, -1.0),
+            rotation: Quaternion::from_axis_angle(Vector3::from_xyz(0.0, 1.0, 0.0), 0.3),
+            scale: Vector3::from_xyz(1.0, 2.0, -3.0),
+        };
+        let matrix = transform.matrix();
+        assert!((matrix.determinant() + 6.0).abs() < 1e-5);
+
+        let decomposed = matrix.decompose();
+        assert!(decomposed.scale.x() < 0.0);
+        assert!(decomposed.scale.y() > 0.0 && decomposed.scale.z() > 0.0);
+        assert_close(&decomposed.matrix(), &matrix);
+        assert_close(&matrix.inverse().unwrap(), &transform.reverse_matrix());
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert!(Matrix::zero().inverse().is_none());
+        assert!(Matrix::scale_vec(Vector3::from_xyz(1.0, 0.0, 1.0))
+            .inverse()
+            .is_none());
+    }
+
+    #[test]
+    fn zero_scaled_axis_keeps_the_rotation() {
+        let rotation = Quaternion::from_axis_angle(Vector3::from_xyz(1.0, 1.0, 0.0), 1.2);
+        for scale in [
+            Vector3::from_xyz(0.0, 2.0, 1.0),
+            Vector3::from_xyz(2.0, 0.0, 1.0),
+            Vector3::from_xyz(2.0, 1.0, 0.0),
+        ] {
+            let transform = RawTransform {
+                position: Vector3::from_xyz(3.0, 2.0, 1.0),
+                rotation,
+                scale,
+            };
+            let matrix = transform.matrix();
+            assert!(matrix.inverse().is_none());
+
+            let decomposed = matrix.decompose();
+            assert!(decomposed.rotation.dot(rotation).abs() > 1.0 - 1e-6);
+            assert_close(&decomposed.matrix(), &matrix);
+        }
+    }
+}
@@ -1,9 +1,17 @@
 pub mod aabb;
+pub mod broadphase;
+pub mod capsule;
+pub mod frustum;
 pub mod matrix;
+pub mod obb;
+pub mod quaternion;
 pub mod raycast;
+pub mod sphere;
 pub mod transform;
+pub mod triangle_bvh;
 pub mod vector;
 
 pub use matrix::Matrix;
+pub use quaternion::Quaternion;
 pub use transform::Transform;
 pub use vector::{Vector2, Vector3};
@@ -0,0 +1,217 @@
+use super::{aabb::Aabb, raycast::Ray, transform::RawTransform, Matrix, Transform, Vector3};
+
+/// Box that follows the rotation of its `center` transform.
+///
+/// Like for `Aabb`, `half_size` is in world units and the scale of the transform is ignored.
+#[derive(Clone)]
+pub struct Obb {
+    pub center: Transform,
+    pub half_size: Vector3,
+}
+
+/// Box in world space, the axes are unit and orthogonal.
+pub(super) struct BoxFrame {
+    center: Vector3,
+    axes: [Vector3; 3],
+    half_size: Vector3,
+}
+
+impl Obb {
+    pub fn new(center: Transform, size: Vector3) -> Self {
+        Self {
+            center,
+            half_size: size * 0.5,
+        }
+    }
+
+    /// World directions of the box edges, unit length.
+    pub fn axes(&self) -> [Vector3; 3] {
+        let matrix = self.center.matrix();
+        let origin = matrix * Vector3::zero();
+        let axis =
+            |x: f32, y: f32, z: f32| (matrix * Vector3::from_xyz(x, y, z) - origin).normalized();
+        [
+            axis(1.0, 0.0, 0.0),
+            axis(0.0, 1.0, 0.0),
+            axis(0.0, 0.0, 1.0),
+        ]
+    }
+
+    /// Corners of the axis-aligned box around the turned one.
+    pub fn bounds(&self) -> (Vector3, Vector3) {
+        let axes = self.axes();
+        let mut extent = Vector3::zero();
+        for i in 0..3 {
+            extent.set(
+                i,
+                (0..3)
+                    .map(|k| axes[k].get(i).abs() * self.half_size.get(k))
+                    .fold(0.0, |acc, it| acc + it),
+            );
+        }
+        let center = self.center.position();
+        (center - extent, center + extent)
+    }
+
+    pub fn frame_matrix(&self) -> Matrix {
+        let mut tr = RawTransform::new();
+        tr.scale = self.half_size;
+        tr.position = self.center.position();
+        tr.rotation = self.center.matrix().decompose().rotation;
+        tr.reverse_matrix()
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.find_mtv(other).is_some()
+    }
+
+    pub fn intersects_aabb(&self, other: &Aabb) -> bool {
+        self.find_mtv_aabb(other).is_some()
+    }
+
+    /// Shortest translation that moves `self` out of `other`, `None` if they do not overlap.
+    pub fn find_mtv(&self, other: &Self) -> Option<Vector3> {
+        self.frame().find_mtv(&other.frame())
+    }
+
+    pub fn find_mtv_aabb(&self, other: &Aabb) -> Option<Vector3> {
+        self.frame().find_mtv(&BoxFrame::from_aabb(other))
+    }
+
+    /// Distance along the ray and the point where it enters the box.
+    pub fn cast_ray(&self, ray: &Ray) -> Option<(f32, Vector3)> {
+        let frame = self.frame();
+        let offset = ray.point - frame.center;
+
+        let mut near = f32::NEG_INFINITY;
+        let mut far = f32::INFINITY;
+        for (i, axis) in frame.axes.iter().enumerate() {
+            let start = offset.dot(*axis);
+            let direction = ray.direction.dot(*axis);
+            let half = frame.half_size.get(i);
+            if direction.abs() < f32::EPSILON {
+                if start.abs() > half {
+                    return None;
+                }
+                continue;
+            }
+            let a = (-half - start) / direction;
+            let b = (half - start) / direction;
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+
+        if near > far || far < 0.0 {
+            return None;
+        }
+        let dist = near.max(0.0);
+        Some((dist, ray.point + ray.direction * dist))
+    }
+
+    pub(super) fn frame(&self) -> BoxFrame {
+        BoxFrame {
+            center: self.center.position(),
+            axes: self.axes(),
+            half_size: self.half_size,
+        }
+    }
+}
+
+impl BoxFrame {
+    pub(super) fn from_aabb(aabb: &Aabb) -> Self {
+        Self {
+            center: aabb.center.position(),
+            axes: [
+                Vector3::from_xyz(1.0, 0.0, 0.0),
+                Vector3::from_xyz(0.0, 1.0, 0.0),
+                Vector3::from_xyz(0.0, 0.0, 1.0),
+            ],
+            half_size: aabb.half_size,
+        }
+    }
+
+    /// Distance from the surface, negative inside of the box.
+    pub(super) fn signed_distance(&self, point: Vector3) -> f32 {
+        let q = self.local(point);
+        let outside = Vector3::from_xyz(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0));
+        outside.length() + q.x().max(q.y()).max(q.z()).min(0.0)
+    }
+
+    /// Shortest translation that moves a ball out of the box.
+    pub(super) fn ball_mtv(&self, point: Vector3, radius: f32) -> Option<Vector3> {
+        let offset = point - self.center;
+        let local = self.axes.map(|axis| offset.dot(axis));
+        let q = self.local(point);
+
+        if q.x() <= 0.0 && q.y() <= 0.0 && q.z() <= 0.0 {
+            // The center is inside or on the surface, leave through the nearest face
+            let i = (0..3)
+                .max_by(|a, b| q.get(*a).total_cmp(&q.get(*b)))
+                .unwrap();
+            let sign = if local[i] < 0.0 { -1.0 } else { 1.0 };
+            return Some(self.axes[i] * (sign * (radius - q.get(i))));
+        }
+
+        let mut away = Vector3::zero();
+        for (i, (axis, coord)) in self.axes.iter().zip(local).enumerate() {
+            let half = self.half_size.get(i);
+            away += *axis * (coord - coord.clamp(-half, half));
+        }
+        let distance = away.length();
+        if distance >= radius {
+            return None;
+        }
+        Some(away * ((radius - distance) / distance))
+    }
+
+    /// How far the point is outside of the box along every axis, negative inside.
+    fn local(&self, point: Vector3) -> Vector3 {
+        let offset = point - self.center;
+        let mut q = Vector3::zero();
+        for i in 0..3 {
+            q.set(i, offset.dot(self.axes[i]).abs() - self.half_size.get(i));
+        }
+        q
+    }
+
+    /// Separating axis test over the face normals of both boxes and the cross products of
+    /// their edges. Face normals go first, so resting contacts push along a face.
+    fn find_mtv(&self, other: &Self) -> Option<Vector3> {
+        let mut candidates = Vec::with_capacity(15);
+        candidates.extend(self.axes);
+        candidates.extend(other.axes);
+        for a in self.axes {
+            for b in other.axes {
+                let cross = a.cross(b);
+                // Parallel edges add nothing the face normals do not cover
+                if cross.length() > 1e-3 {
+                    candidates.push(cross.normalized());
+                }
+            }
+        }
+
+        let distance = other.center - self.center;
+        let mut min = f32::INFINITY;
+        let mut vector = None;
+        for axis in candidates {
+            let separation = distance.dot(axis);
+            let overlap = self.radius(axis) + other.radius(axis) - separation.abs();
+            if overlap <= 0.0 {
+                return None;
+            }
+            if overlap < min {
+                min = overlap;
+                let sign = if separation > 0.0 { -1.0 } else { 1.0 };
+                vector = Some(axis * (overlap * sign));
+            }
+        }
+        vector
+    }
+
+    /// Half of the length of the box projected on `axis`.
+    fn radius(&self, axis: Vector3) -> f32 {
+        (0..3)
+            .map(|i| self.half_size.get(i) * self.axes[i].dot(axis).abs())
+            .fold(0.0, |acc, it| acc + it)
+    }
+}
@@ -0,0 +1,225 @@
+use std::ops::Mul;
+
+use super::{Matrix, Vector3};
+
+/// Rotation as a unit quaternion, the vector part goes first like in glTF.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quaternion {
+    pub fn identity() -> Self {
+        Self::from_xyzw(0.0, 0.0, 0.0, 1.0)
+    }
+
+    pub fn from_xyzw(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+
+    /// Right-handed rotation by `angle` radians around `axis`, which does not have to be unit.
+    pub fn from_axis_angle(axis: Vector3, angle: f32) -> Self {
+        let length = axis.length();
+        if length == 0.0 {
+            return Self::identity();
+        }
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        let axis = axis * (sin / length);
+        Self::from_xyzw(axis.x(), axis.y(), axis.z(), cos)
+    }
+
+    /// Rotation of `RawTransform` Euler angles, its matrix is `Ry(-y) * Rz(-z) * Rx(-x)`
+    /// in terms of the usual right-handed rotation matrices.
+    pub fn from_euler(angles: Vector3) -> Self {
+        let axis = |x: f32, y: f32, z: f32, angle: f32| {
+            Self::from_axis_angle(Vector3::from_xyz(x, y, z), -angle)
+        };
+        axis(0.0, 1.0, 0.0, angles.y())
+            * axis(0.0, 0.0, 1.0, angles.z())
+            * axis(1.0, 0.0, 0.0, angles.x())
+    }
+
+    /// Counterpart of `from_euler`, the angles around x and y are in `-PI..=PI`
+    /// and the one around z in `-PI / 2..=PI / 2`.
+    pub fn to_euler(self) -> Vector3 {
+        let m = self.to_rotation();
+        // More precise than the arcsine of m[1][0] close to the gimbal lock
+        let z = m[1][0].atan2(m[0][0].hypot(m[2][0]));
+        let (x, y) = if z.cos() > 1e-6 {
+            ((-m[1][2]).atan2(m[1][1]), (-m[2][0]).atan2(m[0][0]))
+        } else {
+            // Gimbal lock, the rotation around x merges into the one around y
+            (0.0, m[0][2].atan2(m[2][2]))
+        };
+        Vector3::from_xyz(-x, -y, -z)
+    }
+
+    /// Rotation part of a matrix without scale or shear, row-major.
+    pub fn from_rotation(m: &[[f32; 3]; 3]) -> Self {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self::from_xyzw(
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+                0.25 * s,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+            Self::from_xyzw(
+                0.25 * s,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[2][1] - m[1][2]) / s,
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+            Self::from_xyzw(
+                (m[0][1] + m[1][0]) / s,
+                0.25 * s,
+                (m[1][2] + m[2][1]) / s,
+                (m[0][2] - m[2][0]) / s,
+            )
+        } else {
+            let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+            Self::from_xyzw(
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                0.25 * s,
+                (m[1][0] - m[0][1]) / s,
+            )
+        };
+        q.normalized()
+    }
+
+    /// Row-major rotation matrix, the quaternion is expected to be unit.
+    pub fn to_rotation(self) -> [[f32; 3]; 3] {
+        let Self { x, y, z, w } = self;
+        [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ]
+    }
+
+    pub fn matrix(self) -> Matrix {
+        let mut matrix = Matrix::ident();
+        for (i, row) in self.to_rotation().iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                matrix.set(i, j, *value);
+            }
+        }
+        matrix
+    }
+
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// Unit quaternion of the same rotation, the identity for a zero quaternion.
+    pub fn normalized(self) -> Self {
+        let length = self.length();
+        if length > 0.0 {
+            self * (1.0 / length)
+        } else {
+            Self::identity()
+        }
+    }
+
+    /// Inverse rotation of a unit quaternion.
+    pub fn conjugate(self) -> Self {
+        Self::from_xyzw(-self.x, -self.y, -self.z, self.w)
+    }
+
+    pub fn rotate(self, v: Vector3) -> Vector3 {
+        let rotated = self * Self::from_xyzw(v.x(), v.y(), v.z(), 0.0) * self.conjugate();
+        Vector3::from_xyz(rotated.x, rotated.y, rotated.z)
+    }
+
+    /// Blends towards `other` along the shortest arc at a constant speed.
+    pub fn slerp(self, mut other: Self, t: f32) -> Self {
+        let mut cos = self.dot(other);
+        if cos < 0.0 {
+            other = other * -1.0;
+            cos = -cos;
+        }
+        let (ka, kb) = if cos > 0.9995 {
+            // Nearly the same rotation, plain blending avoids dividing by a tiny sine
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Self::from_xyzw(
+            self.x * ka + other.x * kb,
+            self.y * ka + other.y * kb,
+            self.z * ka + other.z * kb,
+            self.w * ka + other.w * kb,
+        )
+        .normalized()
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// Rotation by `rhs` followed by `self`.
+impl Mul<Quaternion> for Quaternion {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let (a, b) = (self, rhs);
+        Self::from_xyzw(
+            a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+            a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+        )
+    }
+}
+
+impl Mul<f32> for Quaternion {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self {
+        Self::from_xyzw(self.x * rhs, self.y * rhs, self.z * rhs, self.w * rhs)
+    }
+}
+
+impl Mul<Vector3> for Quaternion {
+    type Output = Vector3;
+
+    fn mul(self, rhs: Vector3) -> Vector3 {
+        self.rotate(rhs)
+    }
+}
+
+impl From<[f32; 4]> for Quaternion {
+    fn from([x, y, z, w]: [f32; 4]) -> Self {
+        Self::from_xyzw(x, y, z, w)
+    }
+}
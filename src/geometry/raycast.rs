@@ -54,3 +54,89 @@ pub fn det(a: Vector3, b: Vector3, c: Vector3) -> f32 {
     let d3 = c.x() * (a.y() * b.z() - a.z() * b.y());
     d1 + d2 + d3
 }
+
+/// Distance along the ray and the barycentric coordinates `(u, v)` of the hit, weights of
+/// `b` and `c`. Both sides of the triangle are hit, hits behind the ray start are not.
+pub fn cast_ray_triangle(ray: &Ray, a: Vector3, b: Vector3, c: Vector3) -> Option<(f32, f32, f32)> {
+    let ab = b - a;
+    let ac = c - a;
+    let p = ray.direction.cross(ac);
+    let delta = ab.dot(p);
+    if delta.abs() < 1e-12 {
+        // The ray is parallel to the triangle
+        return None;
+    }
+
+    let r = ray.point - a;
+    let u = r.dot(p) / delta;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = r.cross(ab);
+    let v = ray.direction.dot(q) / delta;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let dist = ac.dot(q) / delta;
+    if dist < 0.0 {
+        return None;
+    }
+    Some((dist, u, v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corners() -> (Vector3, Vector3, Vector3) {
+        (
+            Vector3::from_xyz(0.0, 0.0, 0.0),
+            Vector3::from_xyz(2.0, 0.0, 0.0),
+            Vector3::from_xyz(0.0, 4.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn triangle_hits_give_distance_and_barycentrics() {
+        let (a, b, c) = corners();
+        for z in [3.0, -3.0] {
+            // Both sides are hit
+            let ray = Ray::new(
+                Vector3::from_xyz(0.5, 2.0, z),
+                Vector3::from_xyz(0.0, 0.0, -z / 1.5),
+            );
+            let (dist, u, v) = cast_ray_triangle(&ray, a, b, c).unwrap();
+            assert!((dist - 1.5).abs() < 1e-6);
+            assert!((u - 0.25).abs() < 1e-6 && (v - 0.5).abs() < 1e-6);
+
+            let point = ray.point + ray.direction * dist;
+            let blended = a * (1.0 - u - v) + b * u + c * v;
+            assert!((point - blended).length() < 1e-6);
+        }
+
+        // Each corner has all the weight
+        for (corner, weights) in [(a, (0.0, 0.0)), (b, (1.0, 0.0)), (c, (0.0, 1.0))] {
+            let ray = Ray::new(
+                corner + Vector3::from_xyz(0.0, 0.0, 1.0),
+                Vector3::from_xyz(0.0, 0.0, -1.0),
+            );
+            let (_, u, v) = cast_ray_triangle(&ray, a, b, c).unwrap();
+            assert!((u - weights.0).abs() < 1e-6 && (v - weights.1).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn triangle_misses() {
+        let (a, b, c) = corners();
+        let down = Vector3::from_xyz(0.0, 0.0, -1.0);
+        let outside = Ray::new(Vector3::from_xyz(1.5, 2.0, 1.0), down);
+        assert!(cast_ray_triangle(&outside, a, b, c).is_none());
+        let behind = Ray::new(Vector3::from_xyz(0.5, 0.5, -1.0), down);
+        assert!(cast_ray_triangle(&behind, a, b, c).is_none());
+        let parallel = Ray::new(
+            Vector3::from_xyz(-1.0, 0.5, 0.0),
+            Vector3::from_xyz(1.0, 0.0, 0.0),
+        );
+        assert!(cast_ray_triangle(&parallel, a, b, c).is_none());
+    }
+}
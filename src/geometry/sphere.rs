@@ -0,0 +1,92 @@
+use super::{
+    aabb::Aabb,
+    obb::{BoxFrame, Obb},
+    raycast::Ray,
+    transform::RawTransform,
+    Matrix, Transform, Vector3,
+};
+
+/// Ball around the position of its `center` transform, the radius is in world units.
+#[derive(Clone)]
+pub struct Sphere {
+    pub center: Transform,
+    pub radius: f32,
+}
+
+impl Sphere {
+    pub fn new(center: Transform, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// Corners of the box around the sphere.
+    pub fn bounds(&self) -> (Vector3, Vector3) {
+        let center = self.center.position();
+        let radius = Vector3::repeat(self.radius);
+        (center - radius, center + radius)
+    }
+
+    /// Frame of the bounding box, for gizmos.
+    pub fn frame_matrix(&self) -> Matrix {
+        let mut tr = RawTransform::new();
+        tr.scale = Vector3::repeat(self.radius);
+        tr.position = self.center.position();
+        tr.reverse_matrix()
+    }
+
+    /// Shortest translation that moves `self` out of `other`, `None` if they do not overlap.
+    pub fn find_mtv(&self, other: &Self) -> Option<Vector3> {
+        balls_mtv(
+            self.center.position(),
+            self.radius,
+            other.center.position(),
+            other.radius,
+        )
+    }
+
+    pub fn find_mtv_aabb(&self, other: &Aabb) -> Option<Vector3> {
+        BoxFrame::from_aabb(other).ball_mtv(self.center.position(), self.radius)
+    }
+
+    pub fn find_mtv_obb(&self, other: &Obb) -> Option<Vector3> {
+        other.frame().ball_mtv(self.center.position(), self.radius)
+    }
+
+    /// Distance along the ray and the point where it enters the sphere.
+    pub fn cast_ray(&self, ray: &Ray) -> Option<(f32, Vector3)> {
+        cast_ray_ball(ray, self.center.position(), self.radius)
+    }
+}
+
+/// Shortest translation that moves ball `a` out of ball `b`, straight up for concentric balls.
+pub(super) fn balls_mtv(a: Vector3, a_radius: f32, b: Vector3, b_radius: f32) -> Option<Vector3> {
+    let away = a - b;
+    let distance = away.length();
+    let overlap = a_radius + b_radius - distance;
+    if overlap <= 0.0 {
+        None
+    } else if distance > 0.0 {
+        Some(away * (overlap / distance))
+    } else {
+        Some(Vector3::from_xyz(0.0, overlap, 0.0))
+    }
+}
+
+/// Nearest non-negative hit of a ray and a ball, zero when the ray starts inside.
+pub(super) fn cast_ray_ball(ray: &Ray, center: Vector3, radius: f32) -> Option<(f32, Vector3)> {
+    let offset = ray.point - center;
+    let a = ray.direction.dot(ray.direction);
+    let b = offset.dot(ray.direction);
+    let c = offset.dot(offset) - radius * radius;
+    if c <= 0.0 {
+        return Some((0.0, ray.point));
+    }
+    let discriminant = b * b - a * c;
+    if a == 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let dist = (-b - discriminant.sqrt()) / a;
+    if dist < 0.0 {
+        return None;
+    }
+    Some((dist, ray.point + ray.direction * dist))
+}
@@ -1,8 +1,13 @@
 use std::cell::RefCell;
-use std::rc::Rc;
+use std::fmt::{self, Display};
+use std::rc::{Rc, Weak};
 
-use crate::geometry::{Matrix, Vector3};
+use crate::geometry::{Matrix, Quaternion, Vector3};
 
+/// Shared transform that may follow a parent.
+///
+/// World matrices are cached and recomputed only after the transform or one of its
+/// ancestors changed, `matrix_updates` tells how many times that happened.
 #[derive(Clone, Debug)]
 pub struct Transform(Rc<RefCell<TransformInternal>>);
 
@@ -15,17 +20,92 @@ impl Transform {
         Self(Rc::new(RefCell::new(TransformInternal {
             raw,
             parent: None,
+            children: vec![],
+            world: None,
+            updates: 0,
         })))
     }
 
     pub fn set_parent(&self, parent: Transform) {
+        self.try_set_parent(parent)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Moves the transform under `parent` keeping its local transform, so it moves
+    /// in the world along with the new parent.
+    pub fn try_set_parent(&self, parent: Transform) -> Result<(), HierarchyError> {
+        self.check_parent(&parent)?;
+        self.unlink();
+        parent.0.borrow_mut().children.push(Rc::downgrade(&self.0));
+        self.0.borrow_mut().parent = Some(parent);
+        self.invalidate();
+        Ok(())
+    }
+
+    pub fn set_parent_keep_world(&self, parent: Transform) {
+        self.try_set_parent_keep_world(parent)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Moves the transform under `parent` without moving it in the world. The local
+    /// transform is recomputed, shear of non-uniformly scaled parents is lost.
+    pub fn try_set_parent_keep_world(&self, parent: Transform) -> Result<(), HierarchyError> {
+        self.check_parent(&parent)?;
+        let local = (parent.reverse_matrix() * self.matrix()).decompose();
+        self.try_set_parent(parent)?;
+        self.set_transform(local);
+        Ok(())
+    }
+
+    /// Makes the transform a root, its local transform becomes the world one.
+    pub fn detach(&self) {
+        if self.unlink() {
+            self.invalidate();
+        }
+    }
+
+    /// Makes the transform a root without moving it in the world.
+    pub fn detach_keep_world(&self) {
+        let world = self.matrix().decompose();
+        self.detach();
+        self.set_transform(world);
+    }
+
+    pub fn parent(&self) -> Option<Transform> {
+        self.0.borrow().parent.clone()
+    }
+
+    /// Transforms directly following this one, in the order they were attached.
+    pub fn children(&self) -> Vec<Transform> {
         let mut this = self.0.borrow_mut();
-        this.parent = Some(parent);
+        this.children.retain(|it| it.strong_count() > 0);
+        this.children
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(Transform)
+            .collect()
+    }
+
+    /// Whether both handles share the same transform.
+    pub fn ptr_eq(&self, other: &Transform) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Whether `self` is `other` or follows it through any number of parents.
+    pub fn is_descendant_of(&self, other: &Transform) -> bool {
+        let mut current = Some(self.clone());
+        while let Some(transform) = current {
+            if transform.ptr_eq(other) {
+                return true;
+            }
+            current = transform.parent();
+        }
+        false
     }
 
     pub fn set_transform(&self, transform: RawTransform) {
-        let mut this = self.0.borrow_mut();
-        this.raw = transform;
+        self.0.borrow_mut().raw = transform;
+        self.invalidate();
     }
 
     pub fn from_xyz_hv(x: f32, y: f32, z: f32, h: f32, v: f32) -> Self {
@@ -47,55 +127,140 @@ impl Transform {
     }
 
     pub fn matrix(&self) -> Matrix {
-        self.0.borrow().matrix()
+        self.world().matrix
     }
 
     pub fn reverse_matrix(&self) -> Matrix {
-        self.0.borrow().reverse_matrix()
+        self.world().reverse_matrix
     }
 
     pub fn normal_matrix(&self) -> Matrix {
-        self.0.borrow().normal_matrix()
+        self.world().normal_matrix
     }
 
     pub fn direction(&self) -> Vector3 {
-        let this = self.0.borrow();
-        this.normal_matrix() * Vector3::from_xyz(0.0, 0.0, -1.0)
+        self.normal_matrix() * Vector3::from_xyz(0.0, 0.0, -1.0)
     }
 
     pub fn position(&self) -> Vector3 {
-        let this = self.0.borrow();
-        this.matrix() * Vector3::from_xyz(0.0, 0.0, 0.0)
+        self.matrix() * Vector3::from_xyz(0.0, 0.0, 0.0)
+    }
+
+    pub fn local_position(&self) -> Vector3 {
+        self.0.borrow().raw.position
+    }
+
+    pub fn set_local_position(&self, position: Vector3) {
+        self.0.borrow_mut().raw.position = position;
+        self.invalidate();
+    }
+
+    /// Moves the transform so that its origin ends up at `position` in the world.
+    pub fn set_world_position(&self, position: Vector3) {
+        let local = match self.parent() {
+            Some(parent) => parent.reverse_matrix() * position,
+            None => position,
+        };
+        self.set_local_position(local);
+    }
+
+    /// Number of times the world matrices were computed, e.g. to check that
+    /// a transform nobody touched is not recomputed every frame.
+    pub fn matrix_updates(&self) -> usize {
+        self.0.borrow().updates
     }
 
     pub fn translate(&self, dx: f32, dy: f32, dz: f32) {
-        let mut this = self.0.borrow_mut();
-        this.raw.translate(dx, dy, dz);
+        self.0.borrow_mut().raw.translate(dx, dy, dz);
+        self.invalidate();
     }
 
     pub fn translate_vec(&self, dv: Vector3) {
-        let mut this = self.0.borrow_mut();
-        this.raw.translate_vec(dv);
+        self.0.borrow_mut().raw.translate_vec(dv);
+        self.invalidate();
     }
 
-    pub fn rotate(&self, rot: Vector3) {
-        let mut this = self.0.borrow_mut();
-        this.raw.rotate(rot)
+    pub fn rotate(&self, rot: Quaternion) {
+        self.0.borrow_mut().raw.rotate(rot);
+        self.invalidate();
     }
 
     pub fn rotate_h(&self, dh: f32) {
-        let mut this = self.0.borrow_mut();
-        this.raw.rotate_h(dh)
+        self.0.borrow_mut().raw.rotate_h(dh);
+        self.invalidate();
     }
 
     pub fn rotate_v(&self, dv: f32) {
-        let mut this = self.0.borrow_mut();
-        this.raw.rotate_v(dv)
+        self.0.borrow_mut().raw.rotate_v(dv);
+        self.invalidate();
     }
 
     pub fn scale(&self, scale_factor: f32) {
+        self.0.borrow_mut().raw.scale(scale_factor);
+        self.invalidate();
+    }
+
+    fn check_parent(&self, parent: &Transform) -> Result<(), HierarchyError> {
+        if parent.ptr_eq(self) {
+            Err(HierarchyError::OwnParent)
+        } else if parent.is_descendant_of(self) {
+            Err(HierarchyError::Cycle)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Forgets the parent on both sides, returns whether there was one.
+    fn unlink(&self) -> bool {
+        let Some(old) = self.0.borrow_mut().parent.take() else {
+            return false;
+        };
+        let this = Rc::downgrade(&self.0);
+        old.0.borrow_mut().children.retain(|it| !it.ptr_eq(&this));
+        true
+    }
+
+    fn world(&self) -> WorldMatrices {
+        if let Some(world) = self.0.borrow().world {
+            return world;
+        }
+
+        let (raw, parent) = {
+            let this = self.0.borrow();
+            (this.raw, this.parent.clone())
+        };
+        let mut world = WorldMatrices {
+            matrix: raw.matrix(),
+            reverse_matrix: raw.reverse_matrix(),
+            normal_matrix: raw.normal_matrix(),
+        };
+        if let Some(parent) = parent {
+            let parent = parent.world();
+            world.matrix = parent.matrix * world.matrix;
+            world.reverse_matrix = world.reverse_matrix * parent.reverse_matrix;
+            world.normal_matrix = parent.normal_matrix * world.normal_matrix;
+        }
+
         let mut this = self.0.borrow_mut();
-        this.raw.scale(scale_factor);
+        this.world = Some(world);
+        this.updates += 1;
+        world
+    }
+
+    /// Drops the cached matrices of the transform and all its descendants. Descendants of
+    /// a transform without a cache have none either, so already invalid subtrees are skipped.
+    fn invalidate(&self) {
+        let children: Vec<_> = {
+            let mut this = self.0.borrow_mut();
+            if this.world.take().is_none() {
+                return;
+            }
+            this.children.retain(|it| it.strong_count() > 0);
+            this.children.iter().filter_map(Weak::upgrade).collect()
+        };
+        for child in children {
+            Transform(child).invalidate();
+        }
     }
 }
 
@@ -105,43 +270,47 @@ impl Default for Transform {
     }
 }
 
-#[derive(Debug)]
-pub struct TransformInternal {
-    raw: RawTransform,
-    parent: Option<Transform>,
+/// Why a transform cannot follow the requested parent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HierarchyError {
+    OwnParent,
+    /// The parent already follows the transform
+    Cycle,
 }
 
-impl TransformInternal {
-    pub fn matrix(&self) -> Matrix {
-        let mut matrix = self.raw.matrix();
-        if let Some(parent) = &self.parent {
-            matrix = parent.matrix() * matrix;
+impl Display for HierarchyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OwnParent => write!(f, "Transform cannot be its own parent"),
+            Self::Cycle => write!(f, "Transform cannot follow one of its descendants"),
         }
-        matrix
     }
+}
 
-    pub fn reverse_matrix(&self) -> Matrix {
-        let mut matrix = self.raw.reverse_matrix();
-        if let Some(parent) = &self.parent {
-            matrix = matrix * parent.reverse_matrix();
-        }
-        matrix
-    }
+impl std::error::Error for HierarchyError {}
 
-    pub fn normal_matrix(&self) -> Matrix {
-        let mut matrix = self.raw.normal_matrix();
-        if let Some(parent) = &self.parent {
-            matrix = parent.normal_matrix() * matrix;
-        }
-        matrix
-    }
+#[derive(Debug)]
+pub struct TransformInternal {
+    raw: RawTransform,
+    parent: Option<Transform>,
+    children: Vec<Weak<RefCell<TransformInternal>>>,
+    /// `None` until the matrices are needed and after any change up the hierarchy
+    world: Option<WorldMatrices>,
+    updates: usize,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
+struct WorldMatrices {
+    matrix: Matrix,
+    reverse_matrix: Matrix,
+    normal_matrix: Matrix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct RawTransform {
     pub position: Vector3,
     pub scale: Vector3,
-    pub rotation: Vector3,
+    pub rotation: Quaternion,
 }
 
 impl RawTransform {
@@ -149,7 +318,7 @@ impl RawTransform {
         Self {
             position: Vector3::zero(),
             scale: Vector3::from_xyz(1.0, 1.0, 1.0),
-            rotation: Vector3::zero(),
+            rotation: Quaternion::identity(),
         }
     }
 
@@ -172,30 +341,20 @@ impl RawTransform {
     }
 
     pub fn matrix(&self) -> Matrix {
-        Matrix::translate(self.position)
-            // 
-            * Matrix::rotation_y(-self.rotation.y())
-            * Matrix::rotation_z(self.rotation.z())
-            * Matrix::rotation_x(self.rotation.x())
-            // //
-            * Matrix::scale_vec(self.scale)
+        Matrix::translate(self.position) * self.rotation.matrix() * Matrix::scale_vec(self.scale)
     }
 
     pub fn reverse_matrix(&self) -> Matrix {
         let sc = self.scale;
         Matrix::scale_vec(Vector3::from_xyz(1.0 / sc.x(), 1.0 / sc.y(), 1.0 / sc.z()))
-            * Matrix::rotation_x(-self.rotation.x())
-            * Matrix::rotation_z(-self.rotation.z())
-            * Matrix::rotation_y(self.rotation.y())
+            * self.rotation.conjugate().matrix()
             * Matrix::translate(self.position * -1.0)
     }
 
     pub fn normal_matrix(&self) -> Matrix {
         let sc = self.scale;
 
-        Matrix::rotation_y(-self.rotation.y())
-            * Matrix::rotation_z(self.rotation.z())
-            * Matrix::rotation_x(self.rotation.x())
+        self.rotation.matrix()
             * Matrix::scale_vec(Vector3::from_xyz(
                 sc.y() * sc.z(),
                 sc.x() * sc.z(),
@@ -215,21 +374,35 @@ impl RawTransform {
         self.position += dv;
     }
 
+    /// Turns around the vertical axis of the parent space, like a head turning left or right.
     pub fn rotate_h(&mut self, dh: f32) {
-        self.rotation += Vector3::from_xyz(0.0, dh, 0.0);
+        let turn = Quaternion::from_axis_angle(Vector3::from_xyz(0.0, 1.0, 0.0), -dh);
+        self.rotation = (turn * self.rotation).normalized();
     }
 
+    /// Turns around the own horizontal axis, like a head looking up or down.
     pub fn rotate_v(&mut self, dv: f32) {
-        self.rotation += Vector3::from_xyz(dv, 0.0, 0.0);
+        let turn = Quaternion::from_axis_angle(Vector3::from_xyz(1.0, 0.0, 0.0), -dv);
+        self.rotation = (self.rotation * turn).normalized();
     }
 
-    pub fn rotate(&mut self, rot: Vector3) {
-        self.rotation += rot;
+    /// Applies `rot` on top of the current rotation, in the parent space.
+    pub fn rotate(&mut self, rot: Quaternion) {
+        self.rotation = (rot * self.rotation).normalized();
     }
 
     pub fn scale(&mut self, scale_factor: f32) {
         self.scale *= scale_factor;
     }
+
+    /// Blends towards `other`, position and scale linearly and rotation along the shortest arc.
+    pub fn interpolate(&self, other: &RawTransform, t: f32) -> RawTransform {
+        Self {
+            position: self.position + (other.position - self.position) * t,
+            scale: self.scale + (other.scale - self.scale) * t,
+            rotation: self.rotation.slerp(other.rotation, t),
+        }
+    }
 }
 
 impl Default for RawTransform {
@@ -237,3 +410,97 @@ impl Default for RawTransform {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain() -> (Transform, Transform, Transform) {
+        let root = Transform::from_raw(RawTransform::from_xyz(1.0, 0.0, 0.0));
+        let child = Transform::from_raw(RawTransform::from_xyz(0.0, 2.0, 0.0));
+        let grandchild = Transform::from_raw(RawTransform::from_xyz(0.0, 0.0, 3.0));
+        child.set_parent(root.clone());
+        grandchild.set_parent(child.clone());
+        (root, child, grandchild)
+    }
+
+    fn world_position(transform: &Transform) -> Vector3 {
+        let matrix = transform.matrix();
+        Vector3::from_xyz(matrix.get(0, 3), matrix.get(1, 3), matrix.get(2, 3))
+    }
+
+    #[test]
+    fn world_matrices_are_computed_once() {
+        let transform = Transform::from_raw(RawTransform::from_xyz(1.0, 2.0, 3.0));
+        assert_eq!(transform.matrix_updates(), 0);
+        transform.matrix();
+        transform.reverse_matrix();
+        transform.matrix();
+        assert_eq!(transform.matrix_updates(), 1);
+
+        transform.translate(1.0, 0.0, 0.0);
+        assert_eq!(transform.matrix_updates(), 1);
+        assert_eq!(world_position(&transform), Vector3::from_xyz(2.0, 2.0, 3.0));
+        assert_eq!(transform.matrix_updates(), 2);
+    }
+
+    #[test]
+    fn parent_changes_invalidate_all_descendants() {
+        let (root, child, grandchild) = chain();
+        assert_eq!(
+            world_position(&grandchild),
+            Vector3::from_xyz(1.0, 2.0, 3.0)
+        );
+        let updates = [&root, &child, &grandchild].map(Transform::matrix_updates);
+        assert_eq!(updates, [1, 1, 1]);
+
+        root.translate(4.0, 0.0, 0.0);
+        assert_eq!(
+            world_position(&grandchild),
+            Vector3::from_xyz(5.0, 2.0, 3.0)
+        );
+        let updates = [&root, &child, &grandchild].map(Transform::matrix_updates);
+        assert_eq!(updates, [2, 2, 2]);
+
+        // Siblings and ancestors of the changed transform keep their matrices
+        let sibling = Transform::new();
+        sibling.set_parent(root.clone());
+        sibling.matrix();
+        child.translate(0.0, 1.0, 0.0);
+        assert_eq!(
+            world_position(&grandchild),
+            Vector3::from_xyz(5.0, 3.0, 3.0)
+        );
+        sibling.matrix();
+        let updates = [&root, &child, &grandchild, &sibling].map(Transform::matrix_updates);
+        assert_eq!(updates, [2, 3, 3, 1]);
+    }
+
+    #[test]
+    fn invalidating_a_dirty_subtree_changes_nothing() {
+        let (root, child, grandchild) = chain();
+        grandchild.matrix();
+
+        root.translate(1.0, 0.0, 0.0);
+        root.translate(1.0, 0.0, 0.0);
+        child.translate(0.0, 1.0, 0.0);
+        assert_eq!(
+            world_position(&grandchild),
+            Vector3::from_xyz(3.0, 3.0, 3.0)
+        );
+        let updates = [&root, &child, &grandchild].map(Transform::matrix_updates);
+        assert_eq!(updates, [2, 2, 2]);
+
+        // The root caches its matrices again while its descendants stay dirty,
+        // the next change still reaches them
+        root.translate(1.0, 0.0, 0.0);
+        root.matrix();
+        root.translate(1.0, 0.0, 0.0);
+        assert_eq!(
+            world_position(&grandchild),
+            Vector3::from_xyz(5.0, 3.0, 3.0)
+        );
+        let updates = [&root, &child, &grandchild].map(Transform::matrix_updates);
+        assert_eq!(updates, [4, 3, 3]);
+    }
+}
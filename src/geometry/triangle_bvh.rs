@@ -0,0 +1,259 @@
+use super::{
+    raycast::{cast_ray_triangle, Ray},
+    Vector3,
+};
+
+/// Triangles per leaf, splitting further costs more box tests than it saves.
+const LEAF_SIZE: usize = 4;
+
+/// Closest triangle a ray hits.
+#[derive(Clone, Copy, Debug)]
+pub struct TriangleHit {
+    /// Index of the triangle, its corners are `indices[3 * triangle..3 * triangle + 3]`
+    pub triangle: usize,
+    pub distance: f32,
+    pub point: Vector3,
+    /// Weights of the three corners, in the order of the indices
+    pub barycentric: Vector3,
+    /// Unit normal of the triangle, on the side its corners go counterclockwise
+    pub normal: Vector3,
+}
+
+/// Bounding volume hierarchy over the triangles of an indexed mesh.
+///
+/// Keeps its own copy of the corners, so it does not borrow the mesh it was built from.
+pub struct TriangleBvh {
+    nodes: Vec<Node>,
+    triangles: Vec<Triangle>,
+}
+
+struct Triangle {
+    corners: [Vector3; 3],
+    index: usize,
+}
+
+struct Node {
+    min: Vector3,
+    max: Vector3,
+    /// First triangle of a leaf, first child of an inner node, the second one follows it
+    start: usize,
+    /// Zero for inner nodes
+    count: usize,
+}
+
+impl TriangleBvh {
+    pub fn new(positions: &[Vector3], indices: &[u32]) -> Self {
+        let triangles = indices
+            .chunks_exact(3)
+            .enumerate()
+            .map(|(index, corners)| Triangle {
+                corners: [0, 1, 2].map(|i| positions[corners[i] as usize]),
+                index,
+            })
+            .collect::<Vec<_>>();
+
+        let mut bvh = Self {
+            nodes: vec![],
+            triangles,
+        };
+        if !bvh.triangles.is_empty() {
+            bvh.nodes.push(Node::empty());
+            bvh.split(0, 0, bvh.triangles.len());
+        }
+        bvh
+    }
+
+    /// Closest hit in front of the ray start.
+    pub fn cast_ray(&self, ray: &Ray) -> Option<TriangleHit> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inverse = Vector3::from_xyz(
+            1.0 / ray.direction.x(),
+            1.0 / ray.direction.y(),
+            1.0 / ray.direction.z(),
+        );
+        // Distance, barycentric coordinates and position of the closest triangle so far
+        let mut closest: Option<(f32, f32, f32, usize)> = None;
+        let mut stack = vec![0];
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            let limit = closest.map_or(f32::INFINITY, |it| it.0);
+            if !node.is_hit(ray, inverse, limit) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.start);
+                stack.push(node.start + 1);
+                continue;
+            }
+            for i in node.start..node.start + node.count {
+                let [a, b, c] = self.triangles[i].corners;
+                if let Some((dist, u, v)) = cast_ray_triangle(ray, a, b, c) {
+                    if closest.is_none_or(|it| dist < it.0) {
+                        closest = Some((dist, u, v, i));
+                    }
+                }
+            }
+        }
+
+        closest.map(|(distance, u, v, i)| {
+            let triangle = &self.triangles[i];
+            let [a, b, c] = triangle.corners;
+            TriangleHit {
+                triangle: triangle.index,
+                distance,
+                point: ray.point + ray.direction * distance,
+                barycentric: Vector3::from_xyz(1.0 - u - v, u, v),
+                normal: (b - a).cross(c - a).normalized(),
+            }
+        })
+    }
+
+    /// Fills `node` with the triangles in `start..end`, halving them along the longest
+    /// side of their centers until they fit into leaves.
+    fn split(&mut self, node: usize, start: usize, end: usize) {
+        let (min, max) = Self::bounds(
+            self.triangles[start..end]
+                .iter()
+                .flat_map(|it| it.corners.iter().copied()),
+        );
+        self.nodes[node] = Node {
+            min,
+            max,
+            start,
+            count: end - start,
+        };
+        if end - start <= LEAF_SIZE {
+            return;
+        }
+
+        let (min, max) = Self::bounds(self.triangles[start..end].iter().map(Triangle::center));
+        let size = max - min;
+        let axis = (0..3)
+            .max_by(|a, b| size.get(*a).total_cmp(&size.get(*b)))
+            .unwrap();
+        let mid = (start + end) / 2;
+        self.triangles[start..end].select_nth_unstable_by(mid - start, |a, b| {
+            a.center().get(axis).total_cmp(&b.center().get(axis))
+        });
+
+        let left = self.nodes.len();
+        self.nodes.push(Node::empty());
+        self.nodes.push(Node::empty());
+        self.nodes[node].start = left;
+        self.nodes[node].count = 0;
+        self.split(left, start, mid);
+        self.split(left + 1, mid, end);
+    }
+
+    fn bounds(points: impl Iterator<Item = Vector3>) -> (Vector3, Vector3) {
+        let mut min = Vector3::repeat(f32::INFINITY);
+        let mut max = Vector3::repeat(f32::NEG_INFINITY);
+        for point in points {
+            for i in 0..3 {
+                min.set(i, min.get(i).min(point.get(i)));
+                max.set(i, max.get(i).max(point.get(i)));
+            }
+        }
+        (min, max)
+    }
+}
+
+impl Triangle {
+    fn center(&self) -> Vector3 {
+        let [a, b, c] = self.corners;
+        (a + b + c) * (1.0 / 3.0)
+    }
+}
+
+impl Node {
+    fn empty() -> Self {
+        Self {
+            min: Vector3::zero(),
+            max: Vector3::zero(),
+            start: 0,
+            count: 0,
+        }
+    }
+
+    /// Slab test, `inverse` holds the reciprocals of the ray direction.
+    fn is_hit(&self, ray: &Ray, inverse: Vector3, limit: f32) -> bool {
+        let mut near = 0.0f32;
+        let mut far = limit;
+        for i in 0..3 {
+            let a = (self.min.get(i) - ray.point.get(i)) * inverse.get(i);
+            let b = (self.max.get(i) - ray.point.get(i)) * inverse.get(i);
+            // NaN for a ray running along a slab side, `min` and `max` skip it
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        near <= far
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic numbers in `[0, 1)`, enough to scatter test geometry.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> f32 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        fn point(&mut self, size: f32) -> Vector3 {
+            Vector3::from_xyz(self.next(), self.next(), self.next()) * size
+        }
+    }
+
+    #[test]
+    fn hits_match_a_linear_scan() {
+        let mut random = Lcg(7);
+        let mut positions = vec![];
+        for _ in 0..300 {
+            let corner = random.point(10.0);
+            positions.extend([
+                corner,
+                corner + random.point(1.0),
+                corner + random.point(1.0),
+            ]);
+        }
+        let indices: Vec<u32> = (0..positions.len() as u32).collect();
+        let bvh = TriangleBvh::new(&positions, &indices);
+
+        let mut hits = 0;
+        for _ in 0..500 {
+            let ray = Ray::new(
+                random.point(12.0) - Vector3::repeat(1.0),
+                random.point(2.0) - Vector3::repeat(1.0),
+            );
+            let expected = positions
+                .chunks_exact(3)
+                .enumerate()
+                .filter_map(|(idx, it)| {
+                    cast_ray_triangle(&ray, it[0], it[1], it[2]).map(|(dist, _, _)| (dist, idx))
+                })
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+
+            let hit = bvh.cast_ray(&ray);
+            assert_eq!(hit.map(|it| (it.distance, it.triangle)), expected);
+            hits += hit.is_some() as usize;
+        }
+        assert!(hits > 50, "only {} rays hit", hits);
+    }
+
+    #[test]
+    fn empty_meshes_are_never_hit() {
+        let bvh = TriangleBvh::new(&[], &[]);
+        let ray = Ray::new(Vector3::zero(), Vector3::from_xyz(0.0, 0.0, 1.0));
+        assert!(bvh.cast_ray(&ray).is_none());
+    }
+}
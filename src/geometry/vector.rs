@@ -1,6 +1,6 @@
 use std::ops::{Add, AddAssign, Deref, Mul, MulAssign, Neg, Sub};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Vector2 {
     pub x: f32,
     pub y: f32,
@@ -40,7 +40,7 @@ impl Mul<f32> for Vector2 {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Vector3 {
     pub vector: [f32; 3],
 }
@@ -87,6 +87,10 @@ impl Vector3 {
         )
     }
 
+    pub fn dot(self, b: Self) -> f32 {
+        self.x() * b.x() + self.y() * b.y() + self.z() * b.z()
+    }
+
     pub fn length(self) -> f32 {
         let v = self;
         (v.x() * v.x() + v.y() * v.y() + v.z() * v.z()).sqrt()
@@ -166,7 +170,7 @@ impl MulAssign<f32> for Vector3 {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Vector4 {
     pub vector: [f32; 4],
 }
@@ -0,0 +1,12 @@
+pub mod app;
+pub mod camera;
+pub mod color;
+pub mod controls;
+pub mod download;
+pub mod entity_manager;
+pub mod geometry;
+pub mod gl_context;
+pub mod light;
+pub mod objects;
+pub mod shaders;
+pub mod world;
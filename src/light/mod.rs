@@ -3,7 +3,7 @@ use web_sys::WebGl2RenderingContext as Gl;
 use std::rc::Rc;
 
 use crate::{
-    geometry::{Transform, Vector3},
+    geometry::{frustum::Frustum, Transform, Vector3},
     objects::texture::Texture,
 };
 
@@ -46,6 +46,14 @@ impl Light {
         }
     }
 
+    /// What the shadow maps see, point lights have one frustum per direction.
+    pub fn frustums(&self) -> Vec<Frustum> {
+        match self {
+            Light::Directional(d) => vec![Frustum::from_matrix(&d.matrix())],
+            Light::Point(p) => p.matrices().iter().map(Frustum::from_matrix).collect(),
+        }
+    }
+
     pub fn diffuse(&self) -> f32 {
         match self {
             Light::Directional(d) => d.diffuse,
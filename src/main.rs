@@ -1,15 +1,4 @@
-pub mod app;
-pub mod camera;
-pub mod color;
-pub mod controls;
-pub mod download;
-pub mod entity_manager;
-pub mod geometry;
-pub mod gl_context;
-pub mod light;
-pub mod objects;
-pub mod shaders;
-pub mod world;
+use lightseeker::app;
 
 fn main() {
     wasm_logger::init(wasm_logger::Config::default());
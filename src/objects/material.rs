@@ -0,0 +1,36 @@
+use std::{collections::HashMap, rc::Rc};
+
+use super::{
+    parsers::material::{Material, MaterialLibrary},
+    texture::Texture,
+};
+
+/// Material with its diffuse map resolved to a loaded texture.
+/// Submeshes whose material has no diffuse map use the object texture.
+pub struct ObjectMaterial {
+    pub material: Material,
+    pub texture: Option<Rc<Texture>>,
+}
+
+impl ObjectMaterial {
+    pub fn new(material: Material, texture: Option<Rc<Texture>>) -> Self {
+        Self { material, texture }
+    }
+
+    pub fn from_library<F>(library: &MaterialLibrary, textures: F) -> HashMap<String, Rc<Self>>
+    where
+        F: Fn(&str) -> Option<Rc<Texture>>,
+    {
+        library
+            .materials
+            .iter()
+            .map(|material| {
+                let texture = material.diffuse_map.as_deref().and_then(&textures);
+                (
+                    material.name.clone(),
+                    Rc::new(Self::new(material.clone(), texture)),
+                )
+            })
+            .collect()
+    }
+}
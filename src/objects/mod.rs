@@ -1,3 +1,4 @@
+pub mod material;
 pub mod object;
 pub mod parsers;
 pub mod particles;
@@ -1,13 +1,14 @@
-use std::rc::Rc;
+use std::{collections::HashMap, rc::Rc};
 
-use crate::geometry::{Matrix, Transform};
+use crate::geometry::{raycast::Ray, triangle_bvh::TriangleBvh, Matrix, Transform, Vector3};
 
 use super::{
+    material::ObjectMaterial,
     parsers::{
-        animation::{Animation, AnimationFrame},
+        animation::{Animation, AnimationClip, AnimationFrame},
         skeleton::{BoneTransform, Skeleton},
     },
-    shape::Shape,
+    shape::{Shape, ShapeHit},
     texture::Texture,
 };
 
@@ -15,9 +16,13 @@ pub struct Object {
     pub shape: Rc<Shape>,
     pub skeleton: Vec<BoneTransform>,
     pub texture: Rc<Texture>,
+    pub materials: HashMap<String, Rc<ObjectMaterial>>,
     pub transform: Transform,
     pub animation: Option<Rc<Animation>>,
-    pub animation_frame: f32,
+    /// Clip of the animation being played, all of its keyframes when `None`
+    pub clip: Option<AnimationClip>,
+    /// Seconds since the clip started
+    pub animation_time: f32,
     pub ignored_by_light: bool,
 }
 
@@ -26,11 +31,13 @@ impl Object {
         Self {
             shape,
             texture,
+            materials: HashMap::new(),
             transform,
             ignored_by_light: false,
             skeleton: vec![],
             animation: None,
-            animation_frame: 0.0,
+            clip: None,
+            animation_time: 0.0,
         }
     }
 
@@ -53,23 +60,120 @@ impl Object {
         &self.skeleton[bone].transform
     }
 
+    pub fn with_materials(mut self, materials: HashMap<String, Rc<ObjectMaterial>>) -> Self {
+        self.materials = materials;
+        self
+    }
+
+    pub fn material(&self, name: Option<&str>) -> Option<&ObjectMaterial> {
+        name.and_then(|it| self.materials.get(it))
+            .map(|it| it.as_ref())
+    }
+
     pub fn with_animation(mut self, animation: Rc<Animation>) -> Self {
         self.animation = Some(animation);
+        self.clip = None;
+        self.animation_time = 0.0;
         self
     }
 
+    /// Starts a clip of the animation from its beginning, false when there is no such clip.
+    pub fn play(&mut self, clip: &str) -> bool {
+        match self.animation.as_ref().and_then(|it| it.clip(clip)) {
+            Some(clip) => {
+                self.clip = Some(clip.clone());
+                self.animation_time = 0.0;
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn tick_animation(&mut self, delta_time: f32) {
-        self.animation_frame += delta_time * 20.0;
+        self.animation_time += delta_time;
     }
 
     pub fn bone_matrices(&self) -> impl Iterator<Item = Matrix> + '_ {
-        let animation = &self.animation.as_ref().unwrap().frames;
-        let frame_idx = (self.animation_frame.floor() as usize) % animation.len();
+        let animation = self.animation.as_ref().unwrap();
+        let pose = match &self.clip {
+            Some(clip) => animation.sample_clip(clip, self.animation_time),
+            None => animation.sample_clip(&animation.whole(), self.animation_time),
+        };
 
-        self.set_pose(&animation[frame_idx]);
+        self.set_pose(&pose);
         self.skeleton.iter().map(|it| it.matrix())
     }
 
+    /// World space box around the shape, `None` for animated objects as their bones
+    /// may move the vertices anywhere.
+    pub fn bounds(&self) -> Option<(Vector3, Vector3)> {
+        if self.has_skeleton() {
+            return None;
+        }
+
+        let (min, max) = self.shape.bounds();
+        let matrix = self.transform.matrix();
+        let mut world_min = Vector3::repeat(f32::INFINITY);
+        let mut world_max = Vector3::repeat(f32::NEG_INFINITY);
+        for corner in 0..8 {
+            let pick = |i: usize| {
+                if corner & (1 << i) == 0 {
+                    min.get(i)
+                } else {
+                    max.get(i)
+                }
+            };
+            let point = matrix * Vector3::from_xyz(pick(0), pick(1), pick(2));
+            for i in 0..3 {
+                world_min.set(i, world_min.get(i).min(point.get(i)));
+                world_max.set(i, world_max.get(i).max(point.get(i)));
+            }
+        }
+        Some((world_min, world_max))
+    }
+
+    /// Closest hit of a world space ray on the triangles of the shape.
+    ///
+    /// Animated objects are hit as they are posed right now, their triangle hierarchy
+    /// is rebuilt on every cast instead of being cached in the shape.
+    pub fn cast_ray(&self, ray: &Ray) -> Option<ShapeHit> {
+        let inverse = self.transform.reverse_matrix();
+        let point = inverse * ray.point;
+        // Same distances along the ray in both spaces as the direction is not normalized
+        let local = Ray::new(point, inverse * (ray.point + ray.direction) - point);
+        let hit = if self.has_skeleton() && self.animation.is_some() {
+            TriangleBvh::new(&self.skinned_points(), self.shape.indices()).cast_ray(&local)
+        } else {
+            self.shape.bvh().cast_ray(&local)
+        }?;
+
+        let mut hit = self.shape.shape_hit(hit);
+        hit.point = self.transform.matrix() * hit.point;
+        let normal = self.transform.normal_matrix();
+        hit.normal = (normal * hit.normal - normal * Vector3::zero()).normalized();
+        Some(hit)
+    }
+
+    /// Vertex positions blended by the bones like the vertex shader does.
+    fn skinned_points(&self) -> Vec<Vector3> {
+        let bones: Vec<Matrix> = std::iter::once(Matrix::ident())
+            .chain(self.bone_matrices())
+            .collect();
+        self.shape
+            .vertices()
+            .iter()
+            .map(|vertex| {
+                (0..4).fold(Vector3::zero(), |point, i| {
+                    let bone = bones
+                        .get(vertex.bones.get(i) as usize)
+                        .copied()
+                        .unwrap_or_else(Matrix::ident);
+                    point + (bone * vertex.point) * vertex.weights.get(i)
+                })
+            })
+            .collect()
+    }
+
     pub fn ignored_by_light(mut self) -> Self {
         self.ignored_by_light = true;
         self
@@ -1,47 +1,356 @@
-use std::rc::Rc;
+use std::{collections::HashMap, fmt::Write, rc::Rc};
 
 use crate::geometry::transform::RawTransform;
 
-use super::{obj_lines, parse_transform, skeleton::Skeleton};
+use super::{
+    obj_lines, parse_f32, skeleton::Skeleton, try_parse_quaternion_transform, try_parse_transform,
+    write_transform, ParseError, ParseResult,
+};
+
+/// Frames per second of files that do not declare their own with `fps`.
+pub const DEFAULT_FRAME_RATE: f32 = 20.0;
 
 pub struct Animation {
     pub frames: Vec<AnimationFrame>,
+    pub frame_rate: f32,
+    pub clips: Vec<AnimationClip>,
+    pub clip_names: HashMap<String, usize>,
     pub skeleton: Rc<Skeleton>,
 }
 
+/// A named range of keyframes played on its own, e.g. `idle`, `walk` or `wave`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationClip {
+    pub name: String,
+    /// First and last frame of the clip on the timeline of the file
+    pub start: f32,
+    pub end: f32,
+    /// Starts over after the last frame instead of holding it
+    pub looping: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct AnimationFrame {
+    /// Position on the timeline, `Animation::frame_rate` of them make a second
+    pub frame: f32,
     pub transforms: Vec<RawTransform>,
 }
 
 impl AnimationFrame {
-    fn new() -> Self {
-        Self { transforms: vec![] }
+    fn new(frame: f32) -> Self {
+        Self {
+            frame,
+            transforms: vec![],
+        }
     }
 }
 
 impl Animation {
     pub fn parse(file: &str, skl: &Rc<Skeleton>) -> Self {
-        let mut frames = vec![];
-        let mut current_frame = AnimationFrame::new();
-        let mut skip = true;
-        for split in obj_lines(file) {
-            match split[0] {
+        Self::try_parse(file, skl).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Keyframes are `fr <frame>` followed by one transform per bone, either `af` with
+    /// Euler angles or `aq` with a quaternion. Frame numbers must increase and may
+    /// leave gaps between keys.
+    /// Clips are declared as `clip <name> <start frame> <end frame> [loop|once]`.
+    pub fn try_parse(file: &str, skl: &Rc<Skeleton>) -> ParseResult<Self> {
+        let mut frames: Vec<AnimationFrame> = vec![];
+        let mut frame_rate = DEFAULT_FRAME_RATE;
+        let mut clips = vec![];
+        for line in obj_lines(file) {
+            match line[0] {
+                "clip" => clips.push(Self::parse_clip(&line[1..]).map_err(|err| line.locate(err))?),
+                "fps" => {
+                    let token = line
+                        .get(1)
+                        .ok_or_else(|| line.error("Expected a frame rate"))?;
+                    frame_rate = parse_f32(token).map_err(|err| line.locate(err))?;
+                    if frame_rate <= 0.0 {
+                        return Err(
+                            line.locate(ParseError::at_token(token, "Frame rate must be positive"))
+                        );
+                    }
+                }
                 "fr" => {
-                    if skip {
-                        skip = false;
-                    } else {
-                        frames.push(current_frame);
-                        current_frame = AnimationFrame::new();
+                    let previous = frames.last().map(|it| it.frame);
+                    let frame = match line.get(1) {
+                        Some(token) => {
+                            let frame = parse_f32(token).map_err(|err| line.locate(err))?;
+                            if previous.is_some_and(|it| frame <= it) {
+                                return Err(line.locate(ParseError::at_token(
+                                    token,
+                                    "Frame numbers must increase",
+                                )));
+                            }
+                            frame
+                        }
+                        None => previous.map_or(1.0, |it| it + 1.0),
+                    };
+                    frames.push(AnimationFrame::new(frame));
+                }
+                "af" => {
+                    let transform =
+                        try_parse_transform(&line[1..]).map_err(|err| line.locate(err))?;
+                    if frames.is_empty() {
+                        frames.push(AnimationFrame::new(1.0));
+                    }
+                    frames.last_mut().unwrap().transforms.push(transform);
+                }
+                "aq" => {
+                    let transform = try_parse_quaternion_transform(&line[1..])
+                        .map_err(|err| line.locate(err))?;
+                    if frames.is_empty() {
+                        frames.push(AnimationFrame::new(1.0));
                     }
+                    frames.last_mut().unwrap().transforms.push(transform);
                 }
-                "af" => current_frame.transforms.push(parse_transform(&split[1..])),
                 _ => continue,
             }
         }
-        frames.push(current_frame);
+
+        let mut animation = Self::new(frames, frame_rate, skl);
+        for clip in clips {
+            if animation.clip_names.contains_key(&clip.name) {
+                return Err(ParseError::at_token(&clip.name, "Duplicate clip name"));
+            }
+            animation = animation.with_clip(clip);
+        }
+        Ok(animation)
+    }
+
+    pub fn new(frames: Vec<AnimationFrame>, frame_rate: f32, skl: &Rc<Skeleton>) -> Self {
         Self {
             frames,
+            frame_rate,
+            clips: vec![],
+            clip_names: HashMap::new(),
             skeleton: skl.clone(),
         }
     }
+
+    pub fn with_clip(mut self, clip: AnimationClip) -> Self {
+        self.clip_names.insert(clip.name.clone(), self.clips.len());
+        self.clips.push(clip);
+        self
+    }
+
+    fn parse_clip(split: &[&str]) -> ParseResult<AnimationClip> {
+        if split.len() < 3 {
+            return Err(ParseError::new(
+                "Expected a clip name, start and end frames",
+            ));
+        }
+        let (start, end) = (parse_f32(split[1])?, parse_f32(split[2])?);
+        if end < start {
+            return Err(ParseError::at_token(split[2], "Clip ends before it starts"));
+        }
+        let looping = match split.get(3) {
+            None | Some(&"loop") => true,
+            Some(&"once") => false,
+            Some(token) => return Err(ParseError::at_token(token, "Expected loop or once")),
+        };
+        Ok(AnimationClip {
+            name: split[0].to_string(),
+            start,
+            end,
+            looping,
+        })
+    }
+
+    /// Text in the `.anim` format.
+    pub fn write(&self) -> String {
+        let mut file = String::new();
+        writeln!(file, "fps {}", self.frame_rate).unwrap();
+        for clip in self.clips.iter() {
+            let mode = if clip.looping { "loop" } else { "once" };
+            writeln!(
+                file,
+                "clip {} {} {} {}",
+                clip.name, clip.start, clip.end, mode
+            )
+            .unwrap();
+        }
+        writeln!(file).unwrap();
+        for frame in self.frames.iter() {
+            writeln!(file, "fr {}", frame.frame).unwrap();
+            for transform in frame.transforms.iter() {
+                writeln!(file, "aq {}", write_transform(transform)).unwrap();
+            }
+            writeln!(file).unwrap();
+        }
+        file
+    }
+
+    /// Time of the first keyframe in seconds.
+    pub fn start(&self) -> f32 {
+        self.frames
+            .first()
+            .map_or(0.0, |it| it.frame / self.frame_rate)
+    }
+
+    /// Time of the last keyframe in seconds.
+    pub fn end(&self) -> f32 {
+        self.frames
+            .last()
+            .map_or(0.0, |it| it.frame / self.frame_rate)
+    }
+
+    /// Seconds the whole animation takes to loop once.
+    pub fn duration(&self) -> f32 {
+        self.clip_length(&self.whole())
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&AnimationClip> {
+        self.clip_names.get(name).map(|idx| &self.clips[*idx])
+    }
+
+    /// All keyframes as one looping clip, what plays when no clip is picked.
+    pub fn whole(&self) -> AnimationClip {
+        AnimationClip {
+            name: String::new(),
+            start: self.frames.first().map_or(0.0, |it| it.frame),
+            end: self.frames.last().map_or(0.0, |it| it.frame),
+            looping: true,
+        }
+    }
+
+    /// Seconds a clip plays, looping ones include their seam.
+    pub fn clip_length(&self, clip: &AnimationClip) -> f32 {
+        let seam = if clip.looping { self.seam(clip) } else { 0.0 };
+        (clip.end - clip.start + seam) / self.frame_rate
+    }
+
+    /// Frames a looping clip takes to blend from its last frame back to its first,
+    /// as long as the step between its last keys, or one frame without them.
+    fn seam(&self, clip: &AnimationClip) -> f32 {
+        let keys = self.frames.partition_point(|it| it.frame <= clip.end);
+        match keys.checked_sub(2).map(|idx| &self.frames[idx..keys]) {
+            Some([a, b]) if a.frame >= clip.start => b.frame - a.frame,
+            _ => 1.0,
+        }
+    }
+
+    /// Time on the timeline after playing `clip` for `elapsed` seconds, looping clips
+    /// go past their end while they blend back to the start.
+    pub fn clip_time(&self, clip: &AnimationClip, elapsed: f32) -> f32 {
+        let length = self.clip_length(clip);
+        let offset = if length <= 0.0 {
+            0.0
+        } else if clip.looping {
+            elapsed.rem_euclid(length)
+        } else {
+            elapsed.clamp(0.0, length)
+        };
+        clip.start / self.frame_rate + offset
+    }
+
+    /// Clips that play once are finished when they reach their last frame, loops never are.
+    pub fn clip_finished(&self, clip: &AnimationClip, elapsed: f32) -> bool {
+        !clip.looping && elapsed * self.frame_rate >= clip.end - clip.start
+    }
+
+    pub fn sample_clip(&self, clip: &AnimationClip, elapsed: f32) -> AnimationFrame {
+        let time = self.clip_time(clip, elapsed);
+        let frame = time * self.frame_rate;
+        if !clip.looping || frame <= clip.end {
+            return self.sample(time);
+        }
+
+        let last = self.sample(clip.end / self.frame_rate);
+        let first = self.sample(clip.start / self.frame_rate);
+        Self::blend(&last, &first, (frame - clip.end) / self.seam(clip), frame)
+    }
+
+    /// Pose at `time` seconds, between the surrounding keyframes positions are blended
+    /// linearly and rotations spherically. Outside of the keyframes the nearest one is held.
+    pub fn sample(&self, time: f32) -> AnimationFrame {
+        let frame = time * self.frame_rate;
+        let next = self.frames.partition_point(|it| it.frame <= frame);
+        if next == 0 || next == self.frames.len() {
+            return match self.frames.get(next.saturating_sub(1)) {
+                Some(key) => key.clone(),
+                None => AnimationFrame::new(frame),
+            };
+        }
+
+        let (a, b) = (&self.frames[next - 1], &self.frames[next]);
+        Self::blend(a, b, (frame - a.frame) / (b.frame - a.frame), frame)
+    }
+
+    fn blend(a: &AnimationFrame, b: &AnimationFrame, t: f32, frame: f32) -> AnimationFrame {
+        AnimationFrame {
+            frame,
+            transforms: a
+                .transforms
+                .iter()
+                .zip(b.transforms.iter())
+                .map(|(a, b)| a.interpolate(b, t))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::Vector3;
+
+    use super::*;
+
+    /// Keys at frames 3, 5, .., 41 at 40 frames per second like `walk.anim`,
+    /// the only bone moves along x by its key index.
+    fn walk_like() -> Animation {
+        let skl = Rc::new(Skeleton::from_file("b root\nbb 0 0 0 0 0 0\n"));
+        let frames = (0..20)
+            .map(|idx| AnimationFrame {
+                frame: 3.0 + idx as f32 * 2.0,
+                transforms: vec![RawTransform::from_xyz(idx as f32, 0.0, 0.0)],
+            })
+            .collect();
+        Animation::new(frames, 40.0, &skl)
+    }
+
+    #[test]
+    fn loop_includes_the_seam() {
+        let anim = walk_like();
+        assert_eq!(anim.duration(), 1.0);
+
+        let x = |elapsed: f32| anim.sample_clip(&anim.whole(), elapsed).transforms[0].position;
+        // Halfway between the last key and the first one
+        assert!((x(0.975).x() - 9.5).abs() < 1e-4, "{:?}", x(0.975));
+        assert!((x(1.0).x() - 0.0).abs() < 1e-4, "{:?}", x(1.0));
+        assert!((x(0.95).x() - 19.0).abs() < 1e-4, "{:?}", x(0.95));
+        assert_eq!(x(0.5), Vector3::from_xyz(10.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn clips_played_once_hold_their_end() {
+        let anim = walk_like().with_clip(AnimationClip {
+            name: "once".to_string(),
+            start: 3.0,
+            end: 41.0,
+            looping: false,
+        });
+        let clip = anim.clip("once").unwrap().clone();
+        assert_eq!(anim.clip_length(&clip), 0.95);
+        assert!(anim.clip_finished(&clip, 0.95));
+        let x = anim.sample_clip(&clip, 2.0).transforms[0].position.x();
+        assert_eq!(x, 19.0);
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let skl = Rc::new(Skeleton::from_file(include_str!(
+            "../../../resources/person.skl"
+        )));
+        let anim = Animation::parse(include_str!("../../../resources/person.anim"), &skl);
+        let written = anim.write();
+        let parsed = Animation::parse(&written, &skl);
+
+        assert_eq!(anim.frame_rate, parsed.frame_rate);
+        assert_eq!(anim.clips, parsed.clips);
+        assert_eq!(anim.clip_names, parsed.clip_names);
+        assert_eq!(anim.frames, parsed.frames);
+        assert_eq!(written, parsed.write());
+    }
 }
@@ -0,0 +1,642 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::geometry::{transform::RawTransform, vector::Vector4, Quaternion, Vector2, Vector3};
+
+use super::{
+    animation::{Animation, AnimationClip, AnimationFrame},
+    gltf::GltfAsset,
+    shape::{IndexedMesh, ObjParser, SubMesh, VertexData},
+    skeleton::{Bone, Skeleton},
+    skinning::{Skinning, SkinningVertex},
+    validation::{AssetProblem, AssetValidator},
+    ParseError, ParseResult,
+};
+
+const MAGIC: [u8; 4] = *b"LSAB";
+const VERSION: u16 = 1;
+
+/// Bytes of a vertex and of a transform, quaternion rotation first
+const VERTEX_SIZE: usize = 19 * 4;
+const TRANSFORM_SIZE: usize = 10 * 4;
+
+const SECTION_MESH: [u8; 4] = *b"MESH";
+const SECTION_SKELETON: [u8; 4] = *b"SKEL";
+const SECTION_SKINNING: [u8; 4] = *b"SKIN";
+const SECTION_ANIMATION: [u8; 4] = *b"ANIM";
+
+/// Preprocessed assets in one binary file.
+///
+/// The file starts with the `LSAB` magic, a `u16` version and a `u16` section count,
+/// followed by a table of `(tag, offset, length)` entries and the sections themselves.
+/// Everything is little-endian, sections with unknown tags are skipped.
+pub struct AssetBundle {
+    pub meshes: Vec<IndexedMesh>,
+    pub skeleton: Option<Rc<Skeleton>>,
+    pub skinning: Option<Skinning>,
+    pub animations: Vec<Rc<Animation>>,
+    pub animation_names: HashMap<String, usize>,
+}
+
+impl AssetBundle {
+    pub fn new() -> Self {
+        Self {
+            meshes: vec![],
+            skeleton: None,
+            skinning: None,
+            animations: vec![],
+            animation_names: HashMap::new(),
+        }
+    }
+
+    /// Converts the text formats, `obj` is stored as a single mesh named `default`.
+    pub fn from_text(
+        obj: &str,
+        skeleton: Option<&str>,
+        skinning: Option<&str>,
+        animations: &[(&str, &str)],
+    ) -> ParseResult<Self> {
+        let skinning = skinning.map(Skinning::try_parse).transpose()?;
+        let mesh = ObjParser::try_parse_mesh(obj, skinning.as_ref())?;
+        let mut bundle = Self::new().with_mesh(IndexedMesh::from_mesh(&mesh));
+        if let Some(skinning) = skinning {
+            bundle = bundle.with_skinning(skinning);
+        }
+
+        if let Some(skeleton) = skeleton {
+            let skeleton = Rc::new(Skeleton::try_from_file(skeleton)?);
+            for (name, file) in animations {
+                let animation = Animation::try_parse(file, &skeleton)?;
+                bundle = bundle.with_animation(name, Rc::new(animation));
+            }
+            bundle = bundle.with_skeleton(skeleton);
+        } else if !animations.is_empty() {
+            return Err(ParseError::new("Animations need a skeleton"));
+        }
+
+        Ok(bundle)
+    }
+
+    /// Keeps everything but materials, those stay in the glTF file.
+    pub fn from_gltf(asset: &GltfAsset) -> Self {
+        let mut bundle = Self::new();
+        for mesh in asset.meshes.meshes.iter() {
+            bundle = bundle.with_mesh(IndexedMesh::from_mesh(mesh));
+        }
+        if let Some(skeleton) = &asset.skeleton {
+            bundle = bundle.with_skeleton(skeleton.clone());
+        }
+        for (name, idx) in asset.animation_names.iter() {
+            bundle.animation_names.insert(name.clone(), *idx);
+        }
+        bundle.animations = asset.animations.clone();
+        bundle
+    }
+
+    pub fn with_mesh(mut self, mesh: IndexedMesh) -> Self {
+        self.meshes.push(mesh);
+        self
+    }
+
+    pub fn with_skeleton(mut self, skeleton: Rc<Skeleton>) -> Self {
+        self.skeleton = Some(skeleton);
+        self
+    }
+
+    pub fn with_skinning(mut self, skinning: Skinning) -> Self {
+        self.skinning = Some(skinning);
+        self
+    }
+
+    /// Animations are written against the bundle skeleton.
+    pub fn with_animation(mut self, name: &str, animation: Rc<Animation>) -> Self {
+        self.animation_names
+            .insert(name.to_string(), self.animations.len());
+        self.animations.push(animation);
+        self
+    }
+
+    pub fn mesh(&self, name: &str) -> Option<&IndexedMesh> {
+        self.meshes.iter().find(|it| it.name == name)
+    }
+
+    pub fn animation(&self, name: &str) -> Option<&Rc<Animation>> {
+        self.animation_names
+            .get(name)
+            .map(|idx| &self.animations[*idx])
+    }
+
+    /// Cross-checks the skeleton, skin, meshes and animations of the bundle.
+    pub fn validate(&self) -> Vec<AssetProblem> {
+        let mut validator = AssetValidator::new();
+        if let Some(skeleton) = &self.skeleton {
+            validator = validator.with_skeleton(skeleton);
+        }
+        if let Some(skinning) = &self.skinning {
+            validator = validator.with_skinning(skinning);
+        }
+        for mesh in self.meshes.iter() {
+            validator = validator.with_indexed_mesh(mesh);
+        }
+        let mut names: Vec<_> = self.animation_names.iter().collect();
+        names.sort_by_key(|(_, idx)| **idx);
+        for (name, idx) in names {
+            validator = validator.with_animation(name, &self.animations[*idx]);
+        }
+        validator.validate()
+    }
+
+    pub fn parse(data: &[u8]) -> Self {
+        Self::try_parse(data).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse(data: &[u8]) -> ParseResult<Self> {
+        let mut header = Reader::new(data);
+        if header.bytes(4)? != MAGIC {
+            return Err(ParseError::new("Not a lightseeker asset bundle"));
+        }
+        let version = header.u16()?;
+        if version != VERSION {
+            return Err(ParseError::new(format!(
+                "Asset bundle version {} is not supported",
+                version
+            )));
+        }
+
+        let count = header.u16()?;
+        let mut sections = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let tag = header.bytes(4)?;
+            let offset = header.u32()? as usize;
+            let length = header.u32()? as usize;
+            let section = offset
+                .checked_add(length)
+                .and_then(|end| data.get(offset..end))
+                .ok_or_else(|| ParseError::new("Asset bundle section is out of bounds"))?;
+            sections.push((tag, section));
+        }
+
+        let mut bundle = Self::new();
+        let mut animations = vec![];
+        for (tag, section) in sections {
+            let mut reader = Reader::new(section);
+            match [tag[0], tag[1], tag[2], tag[3]] {
+                SECTION_MESH => bundle.meshes.push(reader.mesh()?),
+                SECTION_SKELETON => bundle.skeleton = Some(Rc::new(reader.skeleton()?)),
+                SECTION_SKINNING => bundle.skinning = Some(reader.skinning()?),
+                SECTION_ANIMATION => animations.push(reader.animation()?),
+                _ => continue,
+            }
+        }
+
+        if !animations.is_empty() {
+            let skeleton = bundle
+                .skeleton
+                .clone()
+                .ok_or_else(|| ParseError::new("Asset bundle has animations but no skeleton"))?;
+            for (name, frame_rate, frames, clips) in animations {
+                let mut animation = Animation::new(frames, frame_rate, &skeleton);
+                for clip in clips {
+                    animation = animation.with_clip(clip);
+                }
+                bundle = bundle.with_animation(&name, Rc::new(animation));
+            }
+        }
+
+        Ok(bundle)
+    }
+
+    /// Panics on names longer than 65535 bytes, the format stores their length in 16 bits.
+    pub fn write(&self) -> Vec<u8> {
+        let mut sections = vec![];
+        for mesh in self.meshes.iter() {
+            let mut writer = Writer::new();
+            writer.mesh(mesh);
+            sections.push((SECTION_MESH, writer.data));
+        }
+        if let Some(skeleton) = &self.skeleton {
+            let mut writer = Writer::new();
+            writer.skeleton(skeleton);
+            sections.push((SECTION_SKELETON, writer.data));
+        }
+        if let Some(skinning) = &self.skinning {
+            let mut writer = Writer::new();
+            writer.skinning(skinning);
+            sections.push((SECTION_SKINNING, writer.data));
+        }
+        let mut names = vec![""; self.animations.len()];
+        for (name, idx) in self.animation_names.iter() {
+            names[*idx] = name;
+        }
+        for (name, animation) in names.iter().zip(self.animations.iter()) {
+            let mut writer = Writer::new();
+            writer.animation(name, animation);
+            sections.push((SECTION_ANIMATION, writer.data));
+        }
+
+        let mut file = Writer::new();
+        file.data.extend(MAGIC);
+        file.u16(VERSION);
+        file.u16(sections.len() as u16);
+        let mut offset = 8 + 12 * sections.len();
+        for (tag, data) in sections.iter() {
+            file.data.extend(tag);
+            file.u32(offset as u32);
+            file.u32(data.len() as u32);
+            offset += data.len();
+        }
+        for (_, data) in sections {
+            file.data.extend(data);
+        }
+        file.data
+    }
+}
+
+impl Default for AssetBundle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    fn bytes(&mut self, count: usize) -> ParseResult<&'a [u8]> {
+        let bytes = self
+            .offset
+            .checked_add(count)
+            .and_then(|end| self.data.get(self.offset..end))
+            .ok_or_else(|| ParseError::new("Unexpected end of asset bundle"))?;
+        self.offset += count;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> ParseResult<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> ParseResult<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn i32(&mut self) -> ParseResult<i32> {
+        Ok(self.u32()? as i32)
+    }
+
+    fn f32(&mut self) -> ParseResult<f32> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    /// Counts come from the file, so they are checked against the remaining data
+    /// before anything is allocated for them.
+    fn count(&mut self, item_size: usize) -> ParseResult<usize> {
+        let count = self.u32()? as usize;
+        match count.checked_mul(item_size) {
+            Some(size) if size <= self.data.len() - self.offset => Ok(count),
+            _ => Err(ParseError::new(
+                "Asset bundle count exceeds the section size",
+            )),
+        }
+    }
+
+    fn string(&mut self) -> ParseResult<String> {
+        let length = self.u16()? as usize;
+        String::from_utf8(self.bytes(length)?.to_vec())
+            .map_err(|_| ParseError::new("Asset bundle string is not valid UTF-8"))
+    }
+
+    fn vector2(&mut self) -> ParseResult<Vector2> {
+        Ok(Vector2::from_xy(self.f32()?, self.f32()?))
+    }
+
+    fn vector3(&mut self) -> ParseResult<Vector3> {
+        Ok(Vector3::from_xyz(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn vector4(&mut self) -> ParseResult<Vector4> {
+        Ok(Vector4::from_xyzw(
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+        ))
+    }
+
+    fn transform(&mut self) -> ParseResult<RawTransform> {
+        let mut transform = RawTransform::new();
+        transform.rotation =
+            Quaternion::from_xyzw(self.f32()?, self.f32()?, self.f32()?, self.f32()?);
+        transform.position = self.vector3()?;
+        transform.scale = self.vector3()?;
+        Ok(transform)
+    }
+
+    fn mesh(&mut self) -> ParseResult<IndexedMesh> {
+        let name = self.string()?;
+
+        let vertex_count = self.count(VERTEX_SIZE)?;
+        let mut vertices = Vec::with_capacity(vertex_count);
+        for _ in 0..vertex_count {
+            vertices.push(VertexData {
+                point: self.vector3()?,
+                normal: self.vector3()?,
+                texture_coord: self.vector2()?,
+                bones: self.vector4()?,
+                weights: self.vector4()?,
+                color: self.vector3()?,
+            });
+        }
+
+        let index_count = self.count(4)?;
+        let mut indices = Vec::with_capacity(index_count);
+        for _ in 0..index_count {
+            let idx = self.u32()?;
+            if idx as usize >= vertex_count {
+                return Err(ParseError::new(format!(
+                    "Vertex index {} is out of range in mesh {}",
+                    idx, name
+                )));
+            }
+            indices.push(idx);
+        }
+
+        let submesh_count = self.count(2 + 4 + 4)?;
+        let mut submeshes = Vec::with_capacity(submesh_count);
+        for _ in 0..submesh_count {
+            let material = self.string()?;
+            let submesh = SubMesh {
+                material: if material.is_empty() {
+                    None
+                } else {
+                    Some(material)
+                },
+                start: self.u32()? as usize,
+                count: self.u32()? as usize,
+            };
+            if submesh.start + submesh.count > index_count {
+                return Err(ParseError::new(format!(
+                    "Submesh is out of range in mesh {}",
+                    name
+                )));
+            }
+            submeshes.push(submesh);
+        }
+
+        Ok(IndexedMesh {
+            name,
+            vertices,
+            indices,
+            submeshes,
+        })
+    }
+
+    fn skeleton(&mut self) -> ParseResult<Skeleton> {
+        let count = self.count(2 + 4 + TRANSFORM_SIZE)?;
+        let mut skeleton = Skeleton {
+            bones: Vec::with_capacity(count),
+            names: HashMap::new(),
+        };
+        for idx in 0..count {
+            let name = self.string()?;
+            let parent = self.i32()? as isize;
+            if parent < -1 || parent >= count as isize {
+                return Err(ParseError::new(format!("Bone {} has unknown parent", name)));
+            }
+            skeleton.names.insert(name.clone(), idx);
+            skeleton.bones.push(Bone {
+                name,
+                parent,
+                initial_transform: self.transform()?,
+            });
+        }
+        Ok(skeleton)
+    }
+
+    fn skinning(&mut self) -> ParseResult<Skinning> {
+        let count = self.count(8 * 4)?;
+        let mut vertices = Vec::with_capacity(count);
+        for _ in 0..count {
+            vertices.push(SkinningVertex {
+                bones: self.vector4()?,
+                weights: self.vector4()?,
+                discarded: 0.0,
+            });
+        }
+        Ok(Skinning { vertices })
+    }
+
+    fn animation(&mut self) -> ParseResult<(String, f32, Vec<AnimationFrame>, Vec<AnimationClip>)> {
+        let name = self.string()?;
+        let frame_rate = self.f32()?;
+        let frame_count = self.count(4 + 4)?;
+        let mut frames = Vec::with_capacity(frame_count);
+        for _ in 0..frame_count {
+            let frame = self.f32()?;
+            let transform_count = self.count(TRANSFORM_SIZE)?;
+            let mut transforms = Vec::with_capacity(transform_count);
+            for _ in 0..transform_count {
+                transforms.push(self.transform()?);
+            }
+            frames.push(AnimationFrame { frame, transforms });
+        }
+        let clip_count = self.count(2 + 4 + 4 + 2)?;
+        let mut clips = Vec::with_capacity(clip_count);
+        for _ in 0..clip_count {
+            clips.push(AnimationClip {
+                name: self.string()?,
+                start: self.f32()?,
+                end: self.f32()?,
+                looping: self.u16()? != 0,
+            });
+        }
+        Ok((name, frame_rate, frames, clips))
+    }
+}
+
+struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    fn new() -> Self {
+        Self { data: vec![] }
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        let length = u16::try_from(value.len()).unwrap_or_else(|_| {
+            panic!(
+                "Name of {} bytes is too long for an asset bundle",
+                value.len()
+            )
+        });
+        self.u16(length);
+        self.data.extend(value.as_bytes());
+    }
+
+    fn floats(&mut self, values: &[f32]) {
+        for value in values.iter() {
+            self.f32(*value);
+        }
+    }
+
+    fn transform(&mut self, transform: &RawTransform) {
+        let rotation = transform.rotation;
+        self.floats(&[rotation.x, rotation.y, rotation.z, rotation.w]);
+        self.floats(&transform.position);
+        self.floats(&transform.scale);
+    }
+
+    fn mesh(&mut self, mesh: &IndexedMesh) {
+        self.string(&mesh.name);
+        self.u32(mesh.vertices.len() as u32);
+        for vertex in mesh.vertices.iter() {
+            self.floats(&vertex.point);
+            self.floats(&vertex.normal);
+            self.floats(&[vertex.texture_coord.x(), vertex.texture_coord.y()]);
+            self.floats(&[
+                vertex.bones.x(),
+                vertex.bones.y(),
+                vertex.bones.z(),
+                vertex.bones.w(),
+            ]);
+            self.floats(&[
+                vertex.weights.x(),
+                vertex.weights.y(),
+                vertex.weights.z(),
+                vertex.weights.w(),
+            ]);
+            self.floats(&vertex.color);
+        }
+        self.u32(mesh.indices.len() as u32);
+        for idx in mesh.indices.iter() {
+            self.u32(*idx);
+        }
+        self.u32(mesh.submeshes.len() as u32);
+        for submesh in mesh.submeshes.iter() {
+            self.string(submesh.material.as_deref().unwrap_or(""));
+            self.u32(submesh.start as u32);
+            self.u32(submesh.count as u32);
+        }
+    }
+
+    fn skeleton(&mut self, skeleton: &Skeleton) {
+        self.u32(skeleton.bones.len() as u32);
+        for bone in skeleton.bones.iter() {
+            self.string(&bone.name);
+            self.u32(bone.parent as i32 as u32);
+            self.transform(&bone.initial_transform);
+        }
+    }
+
+    fn skinning(&mut self, skinning: &Skinning) {
+        self.u32(skinning.vertices.len() as u32);
+        for vertex in skinning.vertices.iter() {
+            for vector in [vertex.bones, vertex.weights] {
+                self.floats(&[vector.x(), vector.y(), vector.z(), vector.w()]);
+            }
+        }
+    }
+
+    fn animation(&mut self, name: &str, animation: &Animation) {
+        self.string(name);
+        self.f32(animation.frame_rate);
+        self.u32(animation.frames.len() as u32);
+        for frame in animation.frames.iter() {
+            self.f32(frame.frame);
+            self.u32(frame.transforms.len() as u32);
+            for transform in frame.transforms.iter() {
+                self.transform(transform);
+            }
+        }
+        self.u32(animation.clips.len() as u32);
+        for clip in animation.clips.iter() {
+            self.string(&clip.name);
+            self.f32(clip.start);
+            self.f32(clip.end);
+            self.u16(clip.looping as u16);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_round_trip() {
+        let bundle = AssetBundle::from_text(
+            include_str!("../../../resources/walk.obj"),
+            Some(include_str!("../../../resources/walk.skl")),
+            Some(include_str!("../../../resources/walk.skin")),
+            &[("walk", include_str!("../../../resources/walk.anim"))],
+        )
+        .unwrap();
+        let parsed = AssetBundle::parse(&bundle.write());
+
+        assert_eq!(bundle.meshes, parsed.meshes);
+        assert_eq!(bundle.skeleton, parsed.skeleton);
+        // `discarded` weights are not stored
+        let weights = |bundle: &AssetBundle| {
+            let skinning = bundle.skinning.as_ref().unwrap();
+            let vertices = skinning.vertices.iter();
+            vertices
+                .map(|it| (it.bones, it.weights))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(weights(&bundle), weights(&parsed));
+
+        assert_eq!(bundle.animation_names, parsed.animation_names);
+        let (anim, parsed_anim) = (
+            bundle.animation("walk").unwrap(),
+            parsed.animation("walk").unwrap(),
+        );
+        assert_eq!(anim.frame_rate, parsed_anim.frame_rate);
+        assert_eq!(anim.frames, parsed_anim.frames);
+        assert_eq!(anim.clips, parsed_anim.clips);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut data = AssetBundle::new().write();
+        data[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(AssetBundle::try_parse(&data).is_err());
+    }
+
+    #[test]
+    fn rejects_sections_out_of_bounds() {
+        let bundle =
+            AssetBundle::from_text(include_str!("../../../resources/walk.obj"), None, None, &[])
+                .unwrap();
+        let mut data = bundle.write();
+        // Offset and length of the first section, right after its tag
+        data[12..20].fill(0xff);
+        assert!(AssetBundle::try_parse(&data).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "too long")]
+    fn refuses_to_truncate_long_names() {
+        let mut writer = Writer::new();
+        writer.string(&"x".repeat(u16::MAX as usize + 1));
+    }
+}
@@ -0,0 +1,361 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::geometry::{transform::RawTransform, Quaternion, Vector3};
+
+use super::{
+    animation::{Animation, AnimationFrame},
+    obj_lines, parse_f32, parse_usize,
+    skeleton::{Bone, Skeleton},
+    try_parse_point_3, ObjLine, ParseError, ParseResult,
+};
+
+/// Skeleton and motion of a BVH motion-capture file.
+///
+/// Bone and frame transforms are in the space of the whole skeleton like the ones
+/// of `.skl` and `.anim` files, so the animation drives `Object::with_skeleton` as is.
+pub struct BvhAsset {
+    pub skeleton: Rc<Skeleton>,
+    pub animation: Rc<Animation>,
+}
+
+#[derive(Clone, Copy)]
+enum Channel {
+    Position(usize),
+    Rotation(usize),
+}
+
+struct Joint {
+    name: String,
+    parent: Option<usize>,
+    offset: Vector3,
+    channels: Vec<Channel>,
+}
+
+impl BvhAsset {
+    pub fn parse(file: &str, scale: f32) -> Self {
+        Self::try_parse(file, scale).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// `scale` multiplies all offsets and positions, e.g. `0.01` for files in centimeters.
+    pub fn try_parse(file: &str, scale: f32) -> ParseResult<Self> {
+        let mut lines = obj_lines(file);
+        let joints = Self::parse_hierarchy(&mut lines, scale)?;
+        if joints.is_empty() {
+            return Err(ParseError::new("BVH file has no joints"));
+        }
+
+        let mut skeleton = Skeleton {
+            bones: Vec::with_capacity(joints.len()),
+            names: HashMap::new(),
+        };
+        let mut rest = Vec::with_capacity(joints.len());
+        for (idx, joint) in joints.iter().enumerate() {
+            let position = match joint.parent {
+                Some(parent) => rest[parent] + joint.offset,
+                None => joint.offset,
+            };
+            rest.push(position);
+            skeleton.names.insert(joint.name.clone(), idx);
+            skeleton.bones.push(Bone {
+                name: joint.name.clone(),
+                parent: joint.parent.map_or(-1, |it| it as isize),
+                initial_transform: RawTransform::from_xyz(position.x(), position.y(), position.z()),
+            });
+        }
+        let skeleton = Rc::new(skeleton);
+
+        let (frames, frame_rate) = Self::parse_motion(&mut lines, &joints, scale)?;
+        let animation = Animation::new(frames, frame_rate, &skeleton);
+        Ok(Self {
+            skeleton,
+            animation: Rc::new(animation),
+        })
+    }
+
+    fn parse_hierarchy<'a>(
+        lines: &mut impl Iterator<Item = ObjLine<'a>>,
+        scale: f32,
+    ) -> ParseResult<Vec<Joint>> {
+        let mut joints: Vec<Joint> = vec![];
+        let mut names = HashMap::new();
+        // Open blocks, `None` for end sites
+        let mut open: Vec<Option<usize>> = vec![];
+        let mut end_site = false;
+
+        for line in lines.by_ref() {
+            match line[0] {
+                "HIERARCHY" => continue,
+                "ROOT" | "JOINT" => {
+                    let name = line[1..].join(" ");
+                    if name.is_empty() {
+                        return Err(line.error("Joint has no name"));
+                    }
+                    if names.insert(name.clone(), joints.len()).is_some() {
+                        return Err(
+                            line.locate(ParseError::at_token(line[1], "Duplicate joint name"))
+                        );
+                    }
+                    let parent = open.last().copied().flatten();
+                    if (line[0] == "ROOT") != parent.is_none() {
+                        return Err(line.error("Only the top level joints are roots"));
+                    }
+                    joints.push(Joint {
+                        name,
+                        parent,
+                        offset: Vector3::zero(),
+                        channels: vec![],
+                    });
+                    end_site = false;
+                }
+                "End" => end_site = true,
+                "{" => {
+                    if end_site {
+                        open.push(None);
+                    } else if joints.is_empty() {
+                        return Err(line.error("Block opens before any joint"));
+                    } else {
+                        open.push(Some(joints.len() - 1));
+                    }
+                }
+                "}" => {
+                    open.pop().ok_or_else(|| line.error("Unexpected }"))?;
+                    end_site = false;
+                }
+                "OFFSET" => {
+                    let offset = try_parse_point_3(&line[1..]).map_err(|err| line.locate(err))?;
+                    if let Some(Some(joint)) = open.last() {
+                        joints[*joint].offset = offset * scale;
+                    }
+                }
+                "CHANNELS" => {
+                    let joint = match open.last() {
+                        Some(Some(joint)) => *joint,
+                        _ => return Err(line.error("Channels outside of a joint")),
+                    };
+                    let count = line
+                        .get(1)
+                        .ok_or_else(|| line.error("Expected a channel count"))?;
+                    let count = parse_usize(count).map_err(|err| line.locate(err))?;
+                    if line.len() != count + 2 {
+                        return Err(line.error(format!("Expected {} channels", count)));
+                    }
+                    for token in line[2..].iter() {
+                        let channel = Self::parse_channel(token).map_err(|err| line.locate(err))?;
+                        joints[joint].channels.push(channel);
+                    }
+                }
+                "MOTION" => {
+                    if !open.is_empty() {
+                        return Err(line.error("Hierarchy has unclosed blocks"));
+                    }
+                    return Ok(joints);
+                }
+                _ => return Err(line.locate(ParseError::at_token(line[0], "Unknown keyword"))),
+            }
+        }
+        Err(ParseError::new("BVH file has no MOTION section"))
+    }
+
+    fn parse_channel(token: &str) -> ParseResult<Channel> {
+        let axis = match token.chars().next() {
+            Some('X') | Some('x') => 0,
+            Some('Y') | Some('y') => 1,
+            Some('Z') | Some('z') => 2,
+            _ => return Err(ParseError::at_token(token, "Unknown channel")),
+        };
+        match token[1..].to_lowercase().as_str() {
+            "position" => Ok(Channel::Position(axis)),
+            "rotation" => Ok(Channel::Rotation(axis)),
+            _ => Err(ParseError::at_token(token, "Unknown channel")),
+        }
+    }
+
+    fn parse_motion<'a>(
+        lines: &mut impl Iterator<Item = ObjLine<'a>>,
+        joints: &[Joint],
+        scale: f32,
+    ) -> ParseResult<(Vec<AnimationFrame>, f32)> {
+        let channel_count: usize = joints.iter().map(|it| it.channels.len()).sum();
+        let mut frame_count = None;
+        let mut frame_rate = None;
+        let mut frames = vec![];
+
+        for line in lines {
+            match line[0] {
+                "Frames:" => {
+                    let token = line
+                        .get(1)
+                        .ok_or_else(|| line.error("Expected a frame count"))?;
+                    frame_count = Some(parse_usize(token).map_err(|err| line.locate(err))?);
+                }
+                "Frame" if line.get(1) == Some(&"Time:") => {
+                    let token = line
+                        .get(2)
+                        .ok_or_else(|| line.error("Expected a frame time"))?;
+                    let time = parse_f32(token).map_err(|err| line.locate(err))?;
+                    if time <= 0.0 {
+                        return Err(
+                            line.locate(ParseError::at_token(token, "Frame time must be positive"))
+                        );
+                    }
+                    frame_rate = Some(1.0 / time);
+                }
+                _ => {
+                    if line.len() != channel_count {
+                        return Err(line.error(format!(
+                            "Expected {} channel values, got {}",
+                            channel_count,
+                            line.len()
+                        )));
+                    }
+                    let values = line
+                        .iter()
+                        .map(|it| parse_f32(it))
+                        .collect::<ParseResult<Vec<_>>>()
+                        .map_err(|err| line.locate(err))?;
+                    frames.push(AnimationFrame {
+                        frame: frames.len() as f32,
+                        transforms: Self::pose(joints, &values, scale),
+                    });
+                }
+            }
+        }
+
+        match frame_count {
+            Some(count) if count != frames.len() => Err(ParseError::new(format!(
+                "BVH file declares {} frames but has {}",
+                count,
+                frames.len()
+            ))),
+            _ => Ok((
+                frames,
+                frame_rate.ok_or_else(|| ParseError::new("BVH file has no frame time"))?,
+            )),
+        }
+    }
+
+    /// Transforms of all joints in one frame, channels apply in the order they are listed.
+    fn pose(joints: &[Joint], values: &[f32], scale: f32) -> Vec<RawTransform> {
+        let mut world: Vec<(Vector3, Quaternion)> = Vec::with_capacity(joints.len());
+        let mut values = values.iter();
+        for joint in joints.iter() {
+            let mut translation = joint.offset;
+            let mut rotation = Quaternion::identity();
+            for channel in joint.channels.iter() {
+                let value = *values.next().unwrap();
+                match *channel {
+                    Channel::Position(axis) => translation.set(axis, value * scale),
+                    Channel::Rotation(axis) => {
+                        let mut direction = Vector3::zero();
+                        direction.set(axis, 1.0);
+                        rotation =
+                            rotation * Quaternion::from_axis_angle(direction, value.to_radians());
+                    }
+                }
+            }
+            world.push(match joint.parent {
+                Some(parent) => {
+                    let (position, parent_rotation) = world[parent];
+                    (
+                        position + parent_rotation * translation,
+                        (parent_rotation * rotation).normalized(),
+                    )
+                }
+                None => (translation, rotation),
+            });
+        }
+
+        world
+            .into_iter()
+            .map(|(position, rotation)| {
+                let mut transform =
+                    RawTransform::from_xyz(position.x(), position.y(), position.z());
+                transform.rotation = rotation;
+                transform
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARM: &str = "HIERARCHY
+ROOT Hips
+{
+    OFFSET 0 0 0
+    CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+    JOINT Arm
+    {
+        OFFSET 0 10 0
+        CHANNELS 3 Zrotation Xrotation Yrotation
+        End Site
+        {
+            OFFSET 0 5 0
+        }
+    }
+}
+MOTION
+Frames: 2
+Frame Time: 0.04
+0 0 0 0 0 0 0 0 0
+1 2 3 90 0 0 0 0 0
+";
+
+    fn assert_close(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn parses_hierarchy_and_motion() {
+        let bvh = BvhAsset::parse(ARM, 0.1);
+        let skl = &bvh.skeleton;
+        assert_eq!(skl.bones.len(), 2);
+        assert_eq!(skl.names["Arm"], 1);
+        assert_eq!(skl.bones[1].parent, 0);
+        assert_close(skl.bones[0].initial_transform.position, Vector3::zero());
+        assert_close(
+            skl.bones[1].initial_transform.position,
+            Vector3::from_xyz(0.0, 1.0, 0.0),
+        );
+
+        let anim = &bvh.animation;
+        assert_eq!(anim.frames.len(), 2);
+        assert_eq!(anim.frame_rate, 25.0);
+        for (bone, transform) in skl.bones.iter().zip(anim.frames[0].transforms.iter()) {
+            assert_close(transform.position, bone.initial_transform.position);
+        }
+
+        // The root moves and turns a quarter around z, the arm swings along with it
+        let [root, arm] = &anim.frames[1].transforms[..] else {
+            panic!("Expected two transforms");
+        };
+        assert_close(root.position, Vector3::from_xyz(0.1, 0.2, 0.3));
+        assert_close(arm.position, Vector3::from_xyz(-0.9, 0.2, 0.3));
+        assert!(arm.rotation.dot(root.rotation).abs() > 1.0 - 1e-6);
+    }
+
+    #[test]
+    fn rejects_blocks_before_any_joint() {
+        let err = BvhAsset::try_parse("HIERARCHY\n{\nMOTION\nFrames: 0\n", 1.0)
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("Block opens before any joint"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn rejects_missing_frames() {
+        let file = ARM.replace("1 2 3 90 0 0 0 0 0\n", "");
+        let err = BvhAsset::try_parse(&file, 1.0).err().unwrap();
+        assert!(
+            err.to_string().contains("declares 2 frames but has 1"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt::{self, Display};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub file: Option<String>,
+    pub line: usize,
+    pub column: Option<usize>,
+    pub token: Option<String>,
+    pub reason: String,
+}
+
+pub type ParseResult<T> = Result<T, ParseError>;
+
+impl ParseError {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            file: None,
+            line: 0,
+            column: None,
+            token: None,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn at_token(token: &str, reason: impl Into<String>) -> Self {
+        let mut error = Self::new(reason);
+        error.token = Some(token.to_string());
+        error
+    }
+
+    pub fn with_file(mut self, file: &str) -> Self {
+        self.file = Some(file.to_string());
+        self
+    }
+
+    pub fn with_line(mut self, line: usize, column: Option<usize>) -> Self {
+        if self.line == 0 {
+            self.line = line;
+            self.column = column;
+        }
+        self
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.as_deref().unwrap_or("<unknown>"))?;
+        if self.line > 0 {
+            write!(f, ":{}", self.line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        write!(f, ": {}", self.reason)?;
+        if let Some(token) = &self.token {
+            write!(f, " (at `{}`)", token)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,1116 @@
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use serde::Deserialize;
+
+use crate::geometry::{
+    transform::RawTransform, vector::Vector4, Matrix, Quaternion, Vector2, Vector3,
+};
+
+use super::{
+    animation::{Animation, AnimationFrame},
+    material::{Material, MaterialLibrary},
+    normals::SmoothingOptions,
+    shape::{ObjMesh, ObjMeshes, VertexData},
+    skeleton::{Bone, Skeleton},
+    ParseError, ParseResult,
+};
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+const MODE_TRIANGLES: u32 = 4;
+
+/// Animations are baked into keyframes at this rate
+const FRAME_RATE: f32 = 20.0;
+
+/// Everything the renderer needs from a glTF 2.0 file.
+///
+/// Meshes are moved into the scene by the node holding them, skinned meshes stay in their
+/// own space as the inverse bind matrices expect. A mesh held by several nodes is rejected.
+/// Skin weights go straight into mesh vertices like `ObjParser::parse_with_skin` does,
+/// so bone `i + 1` of a vertex is joint `i` of the skin and bone 0 is the identity.
+/// Bones start in the bind pose, the inverse of the inverse bind matrices without shear.
+pub struct GltfAsset {
+    pub meshes: ObjMeshes,
+    pub materials: MaterialLibrary,
+    pub skeleton: Option<Rc<Skeleton>>,
+    pub animations: Vec<Rc<Animation>>,
+    pub animation_names: HashMap<String, usize>,
+}
+
+impl GltfAsset {
+    /// Parses a `.gltf` file, its buffers have to be embedded as base64 data URIs.
+    pub fn parse(file: &str) -> Self {
+        Self::try_parse(file).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse(file: &str) -> ParseResult<Self> {
+        Self::try_parse_with_options(file, &Self::flat_shading())
+    }
+
+    pub fn parse_with_options(file: &str, options: &SmoothingOptions) -> Self {
+        Self::try_parse_with_options(file, options).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// `options` smooth the primitives without normals, the others keep the ones of the file.
+    pub fn try_parse_with_options(file: &str, options: &SmoothingOptions) -> ParseResult<Self> {
+        let document = Document::from_json(file.as_bytes())?;
+        let buffers = document
+            .buffers
+            .iter()
+            .map(|buffer| match &buffer.uri {
+                Some(uri) => decode_data_uri(uri),
+                None => Err(ParseError::new("Buffer without uri outside of a .glb file")),
+            })
+            .collect::<ParseResult<Vec<_>>>()?;
+        Loader::new(document, buffers)?.load(options)
+    }
+
+    /// Parses a binary `.glb` file.
+    pub fn parse_glb(data: &[u8]) -> Self {
+        Self::try_parse_glb(data).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse_glb(data: &[u8]) -> ParseResult<Self> {
+        Self::try_parse_glb_with_options(data, &Self::flat_shading())
+    }
+
+    pub fn parse_glb_with_options(data: &[u8], options: &SmoothingOptions) -> Self {
+        Self::try_parse_glb_with_options(data, options).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse_glb_with_options(
+        data: &[u8],
+        options: &SmoothingOptions,
+    ) -> ParseResult<Self> {
+        if data.len() < 12 || read_u32(data, 0) != GLB_MAGIC {
+            return Err(ParseError::new("Not a glTF binary file"));
+        }
+        if read_u32(data, 4) != 2 {
+            return Err(ParseError::new(format!(
+                "Unsupported glTF binary version {}",
+                read_u32(data, 4)
+            )));
+        }
+
+        let mut json = None;
+        let mut bin = None;
+        let mut offset = 12;
+        while offset + 8 <= data.len() {
+            let length = read_u32(data, offset) as usize;
+            let kind = read_u32(data, offset + 4);
+            let chunk = data
+                .get(offset + 8..offset + 8 + length)
+                .ok_or_else(|| ParseError::new("glTF binary chunk is truncated"))?;
+            match kind {
+                GLB_CHUNK_JSON => json = Some(chunk),
+                GLB_CHUNK_BIN => bin = Some(chunk),
+                _ => {}
+            }
+            offset += 8 + length;
+        }
+
+        let document =
+            Document::from_json(json.ok_or_else(|| ParseError::new("glTF binary has no JSON"))?)?;
+        let buffers = document
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(idx, buffer)| match (&buffer.uri, bin) {
+                (Some(uri), _) => decode_data_uri(uri),
+                (None, Some(bin)) if idx == 0 => Ok(bin.to_vec()),
+                (None, _) => Err(ParseError::new(format!("Buffer {} has no data", idx))),
+            })
+            .collect::<ParseResult<Vec<_>>>()?;
+        Loader::new(document, buffers)?.load(options)
+    }
+
+    /// The glTF specification asks for flat normals where a primitive has none.
+    pub fn flat_shading() -> SmoothingOptions {
+        SmoothingOptions {
+            crease_angle: 0.0,
+            ..SmoothingOptions::default()
+        }
+    }
+
+    pub fn animation(&self, name: &str) -> Option<&Rc<Animation>> {
+        self.animation_names
+            .get(name)
+            .map(|idx| &self.animations[*idx])
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Document {
+    nodes: Vec<Node>,
+    meshes: Vec<Mesh>,
+    accessors: Vec<Accessor>,
+    buffer_views: Vec<BufferView>,
+    buffers: Vec<Buffer>,
+    materials: Vec<MaterialDef>,
+    textures: Vec<TextureDef>,
+    images: Vec<Image>,
+    skins: Vec<Skin>,
+    animations: Vec<AnimationDef>,
+}
+
+impl Document {
+    fn from_json(json: &[u8]) -> ParseResult<Self> {
+        serde_json::from_slice(json)
+            .map_err(|err| ParseError::new(format!("Invalid glTF document: {}", err)))
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Node {
+    name: Option<String>,
+    children: Vec<usize>,
+    mesh: Option<usize>,
+    skin: Option<usize>,
+    matrix: Option<[f32; 16]>,
+    translation: Option<[f32; 3]>,
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Mesh {
+    name: Option<String>,
+    primitives: Vec<Primitive>,
+}
+
+#[derive(Deserialize)]
+struct Primitive {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    #[serde(default = "default_mode")]
+    mode: u32,
+}
+
+fn default_mode() -> u32 {
+    MODE_TRIANGLES
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+    sparse: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct Buffer {
+    uri: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct MaterialDef {
+    name: Option<String>,
+    pbr_metallic_roughness: Option<PbrMetallicRoughness>,
+    normal_texture: Option<TextureInfo>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct PbrMetallicRoughness {
+    base_color_factor: Option<[f32; 4]>,
+    base_color_texture: Option<TextureInfo>,
+    roughness_factor: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct TextureInfo {
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct TextureDef {
+    source: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct Image {
+    uri: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Skin {
+    joints: Vec<usize>,
+    inverse_bind_matrices: Option<usize>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct AnimationDef {
+    name: Option<String>,
+    channels: Vec<Channel>,
+    samplers: Vec<Sampler>,
+}
+
+#[derive(Deserialize)]
+struct Channel {
+    sampler: usize,
+    target: Target,
+}
+
+#[derive(Deserialize)]
+struct Target {
+    node: Option<usize>,
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct Sampler {
+    input: usize,
+    output: usize,
+    #[serde(default = "default_interpolation")]
+    interpolation: String,
+}
+
+fn default_interpolation() -> String {
+    "LINEAR".to_string()
+}
+
+/// Node transform as glTF stores it.
+#[derive(Clone, Copy)]
+struct Pose {
+    translation: Vector3,
+    rotation: Quaternion,
+    scale: Vector3,
+}
+
+impl Pose {
+    fn of(node: &Node) -> Self {
+        if let Some(m) = node.matrix {
+            return Self::from_matrix(&m);
+        }
+        let [tx, ty, tz] = node.translation.unwrap_or([0.0; 3]);
+        let [sx, sy, sz] = node.scale.unwrap_or([1.0; 3]);
+        Self {
+            translation: Vector3::from_xyz(tx, ty, tz),
+            rotation: node
+                .rotation
+                .map_or_else(Quaternion::identity, Quaternion::from),
+            scale: Vector3::from_xyz(sx, sy, sz),
+        }
+    }
+
+    /// Splits a column-major matrix without shear into its components.
+    fn from_matrix(m: &[f32; 16]) -> Self {
+        let raw = Matrix { matrix: *m }.transpose().decompose();
+        Self {
+            translation: raw.position,
+            rotation: raw.rotation,
+            scale: raw.scale,
+        }
+    }
+
+    /// Moves a mesh vertex from the space of its node into the scene.
+    ///
+    /// Nodes scaled to zero, which exporters use to hide them, only rotate the normals.
+    fn apply(&self, vertex: &mut VertexData) {
+        vertex.point = self.translation + self.rotation * (self.scale * vertex.point);
+        if self.scale.contains(&0.0) {
+            vertex.normal = self.rotation * vertex.normal;
+            return;
+        }
+        let normal = self.rotation
+            * Vector3::from_xyz(
+                vertex.normal.x() / self.scale.x(),
+                vertex.normal.y() / self.scale.y(),
+                vertex.normal.z() / self.scale.z(),
+            );
+        if normal.length() > 0.0 {
+            vertex.normal = normal.normalized();
+        }
+    }
+
+    /// A negative scale turns the mesh inside out, its triangles have to be flipped.
+    fn is_mirrored(&self) -> bool {
+        self.scale.x() * self.scale.y() * self.scale.z() < 0.0
+    }
+
+    /// Pose of a child in the space its parent lives in.
+    fn then(&self, child: &Pose) -> Pose {
+        Pose {
+            translation: self.translation + self.rotation * (self.scale * child.translation),
+            rotation: (self.rotation * child.rotation).normalized(),
+            scale: self.scale * child.scale,
+        }
+    }
+
+    fn raw(&self) -> RawTransform {
+        let mut raw = RawTransform::new();
+        raw.position = self.translation;
+        raw.rotation = self.rotation;
+        raw.scale = self.scale;
+        raw
+    }
+}
+
+struct Loader {
+    document: Document,
+    buffers: Vec<Vec<u8>>,
+    parents: Vec<Option<usize>>,
+    rest: Vec<Pose>,
+    material_names: Vec<String>,
+}
+
+impl Loader {
+    fn new(document: Document, buffers: Vec<Vec<u8>>) -> ParseResult<Self> {
+        let mut parents = vec![None; document.nodes.len()];
+        for (idx, node) in document.nodes.iter().enumerate() {
+            for child in node.children.iter() {
+                let parent = parents
+                    .get_mut(*child)
+                    .ok_or_else(|| ParseError::new(format!("Unknown child node {}", child)))?;
+                *parent = Some(idx);
+            }
+        }
+        for node in 0..parents.len() {
+            let mut ancestor = parents[node];
+            for _ in 0..parents.len() {
+                ancestor = ancestor.and_then(|it| parents[it]);
+            }
+            if ancestor.is_some() {
+                return Err(ParseError::new("glTF node hierarchy has a cycle"));
+            }
+        }
+        let rest = document.nodes.iter().map(Pose::of).collect();
+
+        let mut used = HashSet::new();
+        let material_names = document
+            .materials
+            .iter()
+            .enumerate()
+            .map(|(idx, it)| unique_name(&mut used, it.name.as_deref(), "material", idx))
+            .collect();
+
+        Ok(Self {
+            document,
+            buffers,
+            parents,
+            rest,
+            material_names,
+        })
+    }
+
+    fn load(self, options: &SmoothingOptions) -> ParseResult<GltfAsset> {
+        let skeleton = self.skeleton()?.map(Rc::new);
+
+        let mut animations = vec![];
+        let mut animation_names = HashMap::new();
+        if let Some(skeleton) = &skeleton {
+            let mut used = HashSet::new();
+            for (idx, def) in self.document.animations.iter().enumerate() {
+                let name = unique_name(&mut used, def.name.as_deref(), "animation", idx);
+                animations.push(Rc::new(self.animation(def, skeleton)?));
+                animation_names.insert(name, idx);
+            }
+        }
+
+        Ok(GltfAsset {
+            meshes: self.meshes(options)?,
+            materials: self.materials(),
+            skeleton,
+            animations,
+            animation_names,
+        })
+    }
+
+    fn materials(&self) -> MaterialLibrary {
+        let mut library = MaterialLibrary {
+            materials: vec![],
+            names: HashMap::new(),
+        };
+        for (def, name) in self
+            .document
+            .materials
+            .iter()
+            .zip(self.material_names.iter())
+        {
+            let mut material = Material::new(name);
+            if let Some(pbr) = &def.pbr_metallic_roughness {
+                let [r, g, b, a] = pbr.base_color_factor.unwrap_or([1.0; 4]);
+                material.diffuse = Vector3::from_xyz(r, g, b);
+                material.dissolve = a;
+
+                // Rough approximation of a metallic-roughness surface by Blinn-Phong
+                let roughness = pbr.roughness_factor.unwrap_or(1.0).clamp(0.0, 1.0);
+                material.specular = Vector3::repeat(1.0 - roughness);
+                material.shininess = (2.0 / roughness.powi(4).max(1e-4) - 2.0).clamp(1.0, 1000.0);
+
+                material.diffuse_map = pbr
+                    .base_color_texture
+                    .as_ref()
+                    .and_then(|it| self.image_uri(it));
+            }
+            material.bump_map = def
+                .normal_texture
+                .as_ref()
+                .and_then(|it| self.image_uri(it));
+
+            library.names.insert(name.clone(), library.materials.len());
+            library.materials.push(material);
+        }
+        library
+    }
+
+    /// Only images referenced by path can be loaded as textures.
+    fn image_uri(&self, info: &TextureInfo) -> Option<String> {
+        let source = self.document.textures.get(info.index)?.source?;
+        let uri = self.document.images.get(source)?.uri.as_ref()?;
+        if uri.starts_with("data:") {
+            None
+        } else {
+            Some(uri.clone())
+        }
+    }
+
+    fn meshes(&self, options: &SmoothingOptions) -> ParseResult<ObjMeshes> {
+        let world = self.world_poses(&self.rest);
+        let mut skins = HashSet::new();
+        // Scene pose of every mesh, `None` for skinned ones
+        let mut poses = HashMap::new();
+        for (idx, node) in self.document.nodes.iter().enumerate() {
+            let mesh = match node.mesh {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            match node.skin {
+                Some(0) => {
+                    skins.insert(mesh);
+                }
+                Some(_) => {
+                    return Err(ParseError::new("Only one skin per glTF file is supported"));
+                }
+                None => {}
+            }
+            let pose = node.skin.is_none().then_some(world[idx]);
+            if poses.insert(mesh, pose).is_some() {
+                return Err(ParseError::new(format!(
+                    "Mesh {} is used by several nodes, instancing is not supported",
+                    mesh
+                )));
+            }
+        }
+
+        let mut meshes = ObjMeshes {
+            meshes: vec![],
+            names: HashMap::new(),
+        };
+        let mut used = HashSet::new();
+        for (idx, def) in self.document.meshes.iter().enumerate() {
+            let name = unique_name(&mut used, def.name.as_deref(), "mesh", idx);
+            let mut mesh = ObjMesh::new(&name);
+            let pose = poses.get(&idx).copied().flatten();
+            for primitive in def.primitives.iter() {
+                self.push_primitive(&mut mesh, primitive, skins.contains(&idx), pose)?;
+            }
+            mesh.smooth_normals(options);
+            meshes.names.insert(name, idx);
+            meshes.meshes.push(mesh);
+        }
+        Ok(meshes)
+    }
+
+    fn push_primitive(
+        &self,
+        mesh: &mut ObjMesh,
+        primitive: &Primitive,
+        skinned: bool,
+        pose: Option<Pose>,
+    ) -> ParseResult<()> {
+        // Points and lines have nothing to render as triangles
+        if primitive.mode != MODE_TRIANGLES {
+            return Ok(());
+        }
+
+        let attribute = |name: &str| primitive.attributes.get(name).copied();
+        let points = self.read_accessor(
+            attribute("POSITION")
+                .ok_or_else(|| ParseError::new("Mesh primitive has no POSITION"))?,
+        )?;
+        let normals = attribute("NORMAL")
+            .map(|it| self.read_accessor(it))
+            .transpose()?;
+        let colors = attribute("COLOR_0")
+            .map(|it| self.read_accessor(it))
+            .transpose()?;
+        let texture_coords = attribute("TEXCOORD_0")
+            .map(|it| self.read_accessor(it))
+            .transpose()?;
+        let (joints, weights) = match (attribute("JOINTS_0"), attribute("WEIGHTS_0")) {
+            (Some(joints), Some(weights)) if skinned => (
+                Some(self.read_accessor(joints)?),
+                Some(self.read_accessor(weights)?),
+            ),
+            _ => (None, None),
+        };
+
+        let count = points.len() / 3;
+        let raw_vertex = |idx: usize| VertexData {
+            point: vector3(&points, idx),
+            normal: normals
+                .as_ref()
+                .map(|it| vector3(it, idx))
+                .unwrap_or_else(Vector3::zero),
+            // glTF puts the origin of texture space in the top left corner just like WebGL
+            texture_coord: texture_coords
+                .as_ref()
+                .map(|it| Vector2::from_xy(it[idx * 2] as f32, it[idx * 2 + 1] as f32))
+                .unwrap_or_else(|| Vector2::from_xy(0.0, 0.0)),
+            bones: joints
+                .as_ref()
+                .map(|it| {
+                    let joints = vector4(it, idx);
+                    Vector4::from_xyzw(
+                        joints.x() + 1.0,
+                        joints.y() + 1.0,
+                        joints.z() + 1.0,
+                        joints.w() + 1.0,
+                    )
+                })
+                .unwrap_or_default(),
+            weights: weights
+                .as_ref()
+                .map(|it| vector4(it, idx))
+                .unwrap_or_default(),
+            // RGB or RGBA, the alpha is dropped
+            color: colors
+                .as_ref()
+                .map(|it| {
+                    let size = it.len() / count;
+                    Vector3::from_xyz(
+                        it[idx * size] as f32,
+                        it[idx * size + 1] as f32,
+                        it[idx * size + 2] as f32,
+                    )
+                })
+                .unwrap_or_else(VertexData::default_color),
+        };
+        let vertex = |idx: usize| {
+            let mut vertex = raw_vertex(idx);
+            if let Some(pose) = &pose {
+                pose.apply(&mut vertex);
+            }
+            vertex
+        };
+        let mirrored = pose.is_some_and(|it| it.is_mirrored());
+
+        let indices: Vec<usize> = match primitive.indices {
+            Some(accessor) => self
+                .read_accessor(accessor)?
+                .into_iter()
+                .map(|it| it as usize)
+                .collect(),
+            None => (0..count).collect(),
+        };
+        if let Some(idx) = indices.iter().find(|it| **it >= count) {
+            return Err(ParseError::new(format!(
+                "Vertex index {} is out of range, primitive has {} vertices",
+                idx, count
+            )));
+        }
+
+        let material = primitive
+            .material
+            .and_then(|it| self.material_names.get(it))
+            .cloned();
+        // Primitives without normals are shaded by the smoothing options
+        let smoothing_group = if normals.is_some() { None } else { Some(1) };
+        for triangle in indices.chunks_exact(3) {
+            let (b, c) = if mirrored {
+                (triangle[2], triangle[1])
+            } else {
+                (triangle[1], triangle[2])
+            };
+            mesh.push_triangle(
+                [vertex(triangle[0]), vertex(b), vertex(c)],
+                &material,
+                smoothing_group,
+            );
+        }
+        Ok(())
+    }
+
+    fn skeleton(&self) -> ParseResult<Option<Skeleton>> {
+        let skin = match self.document.skins.first() {
+            Some(skin) => skin,
+            None => return Ok(None),
+        };
+
+        let mut skeleton = Skeleton {
+            bones: vec![],
+            names: HashMap::new(),
+        };
+        let bind = self.bind_pose(skin)?;
+        let mut used = HashSet::new();
+        for (idx, joint) in skin.joints.iter().enumerate() {
+            let node = self
+                .document
+                .nodes
+                .get(*joint)
+                .ok_or_else(|| ParseError::new(format!("Unknown joint node {}", joint)))?;
+            let name = unique_name(&mut used, node.name.as_deref(), "bone", idx);
+
+            let mut parent = self.parents[*joint];
+            while let Some(node) = parent {
+                if skin.joints.contains(&node) {
+                    break;
+                }
+                parent = self.parents[node];
+            }
+
+            skeleton.names.insert(name.clone(), idx);
+            skeleton.bones.push(Bone {
+                name,
+                parent: parent
+                    .and_then(|it| skin.joints.iter().position(|joint| *joint == it))
+                    .map_or(-1, |it| it as isize),
+                initial_transform: bind[idx],
+            });
+        }
+        Ok(Some(skeleton))
+    }
+
+    /// Transforms of the joints the mesh was bound in, identities when the skin has no
+    /// inverse bind matrices.
+    fn bind_pose(&self, skin: &Skin) -> ParseResult<Vec<RawTransform>> {
+        let accessor = match skin.inverse_bind_matrices {
+            Some(accessor) => accessor,
+            None => return Ok(vec![RawTransform::new(); skin.joints.len()]),
+        };
+        let values = self.read_accessor(accessor)?;
+        if values.len() != skin.joints.len() * 16 {
+            return Err(ParseError::new(
+                "Skin needs one inverse bind matrix for every joint",
+            ));
+        }
+        values
+            .chunks_exact(16)
+            .enumerate()
+            .map(|(idx, values)| {
+                let mut matrix = Matrix::zero();
+                for (value, it) in matrix.matrix.iter_mut().zip(values) {
+                    *value = *it as f32;
+                }
+                matrix
+                    .transpose()
+                    .inverse()
+                    .map(|it| it.decompose())
+                    .ok_or_else(|| {
+                        ParseError::new(format!("Inverse bind matrix of joint {} is singular", idx))
+                    })
+            })
+            .collect()
+    }
+
+    fn animation(&self, def: &AnimationDef, skeleton: &Rc<Skeleton>) -> ParseResult<Animation> {
+        let joints = &self.document.skins[0].joints;
+
+        let mut samplers = Vec::with_capacity(def.samplers.len());
+        for sampler in def.samplers.iter() {
+            samplers.push((
+                self.read_accessor(sampler.input)?,
+                self.read_accessor(sampler.output)?,
+            ));
+        }
+        let duration = samplers
+            .iter()
+            .filter_map(|(times, _)| times.last())
+            .fold(0.0, |a: f64, b| a.max(*b)) as f32;
+
+        let frame_count = (duration * FRAME_RATE).ceil() as usize + 1;
+        let mut frames = Vec::with_capacity(frame_count);
+        for frame in 0..frame_count {
+            let time = (frame as f32 / FRAME_RATE).min(duration);
+            let mut local = self.rest.clone();
+            for channel in def.channels.iter() {
+                let node = match channel.target.node {
+                    Some(node) if node < local.len() => node,
+                    _ => continue,
+                };
+                let sampler = def
+                    .samplers
+                    .get(channel.sampler)
+                    .ok_or_else(|| ParseError::new("Animation channel has no sampler"))?;
+                let components = match channel.target.path.as_str() {
+                    "translation" | "scale" => 3,
+                    "rotation" => 4,
+                    // Morph target weights are not supported
+                    _ => continue,
+                };
+                let keys = if sampler.interpolation == "CUBICSPLINE" {
+                    3
+                } else {
+                    1
+                };
+                let (times, values) = &samplers[channel.sampler];
+                if times.is_empty() || values.len() < times.len() * keys * components {
+                    return Err(ParseError::new("Animation sampler has too few values"));
+                }
+
+                let v = sample(times, values, components, &sampler.interpolation, time);
+                let pose = &mut local[node];
+                match components {
+                    4 => pose.rotation = Quaternion::from_xyzw(v[0], v[1], v[2], v[3]).normalized(),
+                    _ if channel.target.path == "scale" => {
+                        pose.scale = Vector3::from_xyz(v[0], v[1], v[2])
+                    }
+                    _ => pose.translation = Vector3::from_xyz(v[0], v[1], v[2]),
+                }
+            }
+
+            let world = self.world_poses(&local);
+            frames.push(AnimationFrame {
+                frame: frame as f32,
+                transforms: joints.iter().map(|joint| world[*joint].raw()).collect(),
+            });
+        }
+
+        Ok(Animation::new(frames, FRAME_RATE, skeleton))
+    }
+
+    /// Poses of all nodes in the space of the scene.
+    fn world_poses(&self, local: &[Pose]) -> Vec<Pose> {
+        let mut world: Vec<Option<Pose>> = vec![None; local.len()];
+        for node in 0..local.len() {
+            let mut chain = vec![node];
+            while let Some(parent) = self.parents[*chain.last().unwrap()] {
+                if world[parent].is_some() {
+                    break;
+                }
+                chain.push(parent);
+            }
+            for idx in chain.into_iter().rev() {
+                if world[idx].is_none() {
+                    world[idx] = Some(match self.parents[idx].and_then(|it| world[it]) {
+                        Some(parent) => parent.then(&local[idx]),
+                        None => local[idx],
+                    });
+                }
+            }
+        }
+        world.into_iter().map(|it| it.unwrap()).collect()
+    }
+
+    /// Reads an accessor as a flat list of its components.
+    fn read_accessor(&self, idx: usize) -> ParseResult<Vec<f64>> {
+        let accessor = self
+            .document
+            .accessors
+            .get(idx)
+            .ok_or_else(|| ParseError::new(format!("Unknown accessor {}", idx)))?;
+        if accessor.sparse.is_some() {
+            return Err(ParseError::new("Sparse accessors are not supported"));
+        }
+
+        let components = match accessor.kind.as_str() {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" | "MAT2" => 4,
+            "MAT3" => 9,
+            "MAT4" => 16,
+            kind => return Err(ParseError::new(format!("Unknown accessor type {}", kind))),
+        };
+        let size = match accessor.component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            kind => {
+                return Err(ParseError::new(format!(
+                    "Unknown accessor component type {}",
+                    kind
+                )))
+            }
+        };
+
+        let view = match accessor.buffer_view {
+            Some(view) => self
+                .document
+                .buffer_views
+                .get(view)
+                .ok_or_else(|| ParseError::new(format!("Unknown buffer view {}", view)))?,
+            // Accessors without a view are filled with zeros
+            None => return Ok(vec![0.0; accessor.count * components]),
+        };
+        let buffer = self
+            .buffers
+            .get(view.buffer)
+            .ok_or_else(|| ParseError::new(format!("Unknown buffer {}", view.buffer)))?;
+        let data = buffer
+            .get(view.byte_offset..view.byte_offset + view.byte_length)
+            .ok_or_else(|| ParseError::new("Buffer view is out of buffer bounds"))?;
+
+        let stride = view.byte_stride.unwrap_or(components * size);
+        let mut values = Vec::with_capacity(accessor.count * components);
+        for element in 0..accessor.count {
+            for component in 0..components {
+                let offset = accessor.byte_offset + element * stride + component * size;
+                let bytes = data
+                    .get(offset..offset + size)
+                    .ok_or_else(|| ParseError::new(format!("Accessor {} is out of bounds", idx)))?;
+                values.push(read_component(
+                    bytes,
+                    accessor.component_type,
+                    accessor.normalized,
+                ));
+            }
+        }
+        Ok(values)
+    }
+}
+
+fn read_component(bytes: &[u8], component_type: u32, normalized: bool) -> f64 {
+    let (value, max) = match component_type {
+        5120 => (bytes[0] as i8 as f64, i8::MAX as f64),
+        5121 => (bytes[0] as f64, u8::MAX as f64),
+        5122 => (
+            i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            i16::MAX as f64,
+        ),
+        5123 => (
+            u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            u16::MAX as f64,
+        ),
+        5125 => (read_u32(bytes, 0) as f64, u32::MAX as f64),
+        _ => return f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+    };
+    if normalized {
+        (value / max).max(-1.0)
+    } else {
+        value
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn vector3(values: &[f64], idx: usize) -> Vector3 {
+    Vector3::from_xyz(
+        values[idx * 3] as f32,
+        values[idx * 3 + 1] as f32,
+        values[idx * 3 + 2] as f32,
+    )
+}
+
+fn vector4(values: &[f64], idx: usize) -> Vector4 {
+    Vector4::from_xyzw(
+        values[idx * 4] as f32,
+        values[idx * 4 + 1] as f32,
+        values[idx * 4 + 2] as f32,
+        values[idx * 4 + 3] as f32,
+    )
+}
+
+/// Names from the file if present, otherwise `prefix{idx}`; duplicates get the index appended.
+fn unique_name(used: &mut HashSet<String>, name: Option<&str>, prefix: &str, idx: usize) -> String {
+    let mut name = match name {
+        Some(name) => name.to_string(),
+        None => format!("{}{}", prefix, idx),
+    };
+    if used.contains(&name) {
+        name = format!("{}.{}", name, idx);
+    }
+    used.insert(name.clone());
+    name
+}
+
+/// Value of an animation sampler at `time`, `values` holds `components` floats per key
+/// and `times` is not empty.
+fn sample(
+    times: &[f64],
+    values: &[f64],
+    components: usize,
+    interpolation: &str,
+    time: f32,
+) -> Vec<f32> {
+    let time = time as f64;
+    let cubic = interpolation == "CUBICSPLINE";
+    // Cubic spline keys are stored as in-tangent, value, out-tangent
+    let value = |key: usize, part: usize| -> Vec<f64> {
+        let start = if cubic {
+            (key * 3 + part) * components
+        } else {
+            key * components
+        };
+        values[start..start + components].to_vec()
+    };
+
+    let next = times
+        .iter()
+        .position(|it| *it > time)
+        .unwrap_or(times.len());
+    if next == 0 || next == times.len() {
+        let key = next.min(times.len() - 1);
+        return value(key, 1).into_iter().map(|it| it as f32).collect();
+    }
+
+    let key = next - 1;
+    let dt = times[next] - times[key];
+    let s = (time - times[key]) / dt;
+    let result: Vec<f64> = match interpolation {
+        "STEP" => value(key, 1),
+        "CUBICSPLINE" => {
+            let (s2, s3) = (s * s, s * s * s);
+            let (a, out_tangent) = (value(key, 1), value(key, 2));
+            let (in_tangent, b) = (value(next, 0), value(next, 1));
+            (0..components)
+                .map(|i| {
+                    (2.0 * s3 - 3.0 * s2 + 1.0) * a[i]
+                        + (s3 - 2.0 * s2 + s) * dt * out_tangent[i]
+                        + (-2.0 * s3 + 3.0 * s2) * b[i]
+                        + (s3 - s2) * dt * in_tangent[i]
+                })
+                .collect()
+        }
+        _ if components == 4 => {
+            let (a, b) = (value(key, 1), value(next, 1));
+            let quaternion = |v: Vec<f64>| {
+                Quaternion::from_xyzw(v[0] as f32, v[1] as f32, v[2] as f32, v[3] as f32)
+            };
+            let slerped = quaternion(a).slerp(quaternion(b), s as f32);
+            return vec![slerped.x, slerped.y, slerped.z, slerped.w];
+        }
+        _ => {
+            let (a, b) = (value(key, 1), value(next, 1));
+            (0..components).map(|i| a[i] + (b[i] - a[i]) * s).collect()
+        }
+    };
+    result.into_iter().map(|it| it as f32).collect()
+}
+
+fn decode_data_uri(uri: &str) -> ParseResult<Vec<u8>> {
+    if !uri.starts_with("data:") {
+        return Err(ParseError::new(format!(
+            "External buffer {} is not supported, embed it or use .glb",
+            uri
+        )));
+    }
+    let data = uri
+        .split_once(";base64,")
+        .map(|(_, data)| data)
+        .ok_or_else(|| ParseError::new("Buffer data uri is not base64"))?;
+    decode_base64(data)
+}
+
+fn decode_base64(data: &str) -> ParseResult<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(data.len() / 4 * 3);
+    let mut accumulator = 0_u32;
+    let mut bits = 0;
+    for c in data.bytes().filter(|it| *it != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => {
+                return Err(ParseError::new(format!(
+                    "Invalid base64 character {:?}",
+                    c as char
+                )))
+            }
+        };
+        accumulator = (accumulator << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((accumulator >> bits) as u8);
+            accumulator &= (1 << bits) - 1;
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::vector::Vector4;
+
+    use super::*;
+
+    fn vertex(normal: Vector3) -> VertexData {
+        VertexData {
+            point: Vector3::from_xyz(1.0, 1.0, 1.0),
+            normal,
+            texture_coord: Vector2::from_xy(0.0, 0.0),
+            bones: Vector4::zero(),
+            weights: Vector4::zero(),
+            color: VertexData::default_color(),
+        }
+    }
+
+    #[test]
+    fn poses_keep_normals_perpendicular() {
+        let pose = Pose {
+            translation: Vector3::from_xyz(1.0, 0.0, 0.0),
+            rotation: Quaternion::identity(),
+            scale: Vector3::from_xyz(2.0, 1.0, 1.0),
+        };
+        // Normal of the plane x + y = 2, which becomes x / 2 + y = 2.5
+        let mut vertex = vertex(Vector3::from_xyz(1.0, 1.0, 0.0).normalized());
+        pose.apply(&mut vertex);
+        assert_eq!(vertex.point, Vector3::from_xyz(3.0, 1.0, 1.0));
+        let expected = Vector3::from_xyz(0.5, 1.0, 0.0).normalized();
+        assert!(
+            (vertex.normal - expected).length() < 1e-6,
+            "{:?}",
+            vertex.normal
+        );
+    }
+
+    #[test]
+    fn hidden_nodes_keep_finite_normals() {
+        let rotation = Quaternion::from_axis_angle(Vector3::from_xyz(0.0, 0.0, 1.0), 0.5);
+        let pose = Pose {
+            translation: Vector3::zero(),
+            rotation,
+            scale: Vector3::from_xyz(0.0, 0.0, 0.0),
+        };
+        let normal = Vector3::from_xyz(0.0, 1.0, 0.0);
+        let mut vertex = vertex(normal);
+        pose.apply(&mut vertex);
+        assert_eq!(vertex.point, Vector3::zero());
+        assert!(
+            (vertex.normal - rotation * normal).length() < 1e-6,
+            "{:?}",
+            vertex.normal
+        );
+    }
+}
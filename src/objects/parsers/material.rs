@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use crate::geometry::Vector3;
+
+use super::{obj_lines, parse_f32, parse_usize, try_parse_point_3, ObjLine, ParseResult};
+
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    pub ambient: Vector3,
+    pub diffuse: Vector3,
+    pub specular: Vector3,
+    pub shininess: f32,
+    pub dissolve: f32,
+    /// Parsed but not rendered yet, the view shader always uses its own lighting
+    pub illumination: usize,
+    pub diffuse_map: Option<String>,
+    /// Parsed but not rendered yet, the view shader has no normal maps
+    pub bump_map: Option<String>,
+}
+
+impl Material {
+    /// Material that renders exactly like an object without any material.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ambient: Vector3::repeat(1.0),
+            diffuse: Vector3::repeat(1.0),
+            specular: Vector3::repeat(1.0),
+            shininess: 100.0,
+            dissolve: 1.0,
+            illumination: 2,
+            diffuse_map: None,
+            bump_map: None,
+        }
+    }
+
+    /// Single specular intensity used by the view shader.
+    pub fn specular_intensity(&self) -> f32 {
+        (self.specular.x() + self.specular.y() + self.specular.z()) / 3.0
+    }
+}
+
+#[derive(Debug)]
+pub struct MaterialLibrary {
+    pub materials: Vec<Material>,
+    pub names: HashMap<String, usize>,
+}
+
+impl MaterialLibrary {
+    pub fn parse(file: &str) -> Self {
+        Self::try_parse(file).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse(file: &str) -> ParseResult<Self> {
+        let mut lib = Self {
+            materials: vec![],
+            names: HashMap::new(),
+        };
+        for line in obj_lines(file) {
+            match line[0] {
+                "newmtl" => {
+                    let name = line
+                        .get(1)
+                        .ok_or_else(|| line.error("Material has no name"))?;
+                    lib.materials.push(Material::new(name));
+                    lib.names.insert(name.to_string(), lib.materials.len() - 1);
+                }
+                "Ka" => lib.last_material(&line)?.ambient = Self::parse_color(&line)?,
+                "Kd" => lib.last_material(&line)?.diffuse = Self::parse_color(&line)?,
+                "Ks" => lib.last_material(&line)?.specular = Self::parse_color(&line)?,
+                "Ns" => lib.last_material(&line)?.shininess = Self::parse_scalar(&line)?,
+                "d" => lib.last_material(&line)?.dissolve = Self::parse_scalar(&line)?,
+                "Tr" => lib.last_material(&line)?.dissolve = 1.0 - Self::parse_scalar(&line)?,
+                "illum" => {
+                    let illumination = line
+                        .get(1)
+                        .ok_or_else(|| line.error("Expected an illumination model"))
+                        .and_then(|it| parse_usize(it).map_err(|err| line.locate(err)))?;
+                    lib.last_material(&line)?.illumination = illumination;
+                }
+                "map_Kd" => lib.last_material(&line)?.diffuse_map = Some(Self::parse_map(&line)?),
+                "map_Bump" | "map_bump" | "bump" => {
+                    lib.last_material(&line)?.bump_map = Some(Self::parse_map(&line)?)
+                }
+                _ => continue,
+            }
+        }
+        Ok(lib)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Material> {
+        self.names.get(name).map(|idx| &self.materials[*idx])
+    }
+
+    fn last_material(&mut self, line: &ObjLine) -> ParseResult<&mut Material> {
+        self.materials
+            .last_mut()
+            .ok_or_else(|| line.error("Material property appears before any material"))
+    }
+
+    fn parse_color(line: &ObjLine) -> ParseResult<Vector3> {
+        // A single component is a shorthand for a gray color
+        if line.len() == 2 {
+            return Self::parse_scalar(line).map(Vector3::repeat);
+        }
+        try_parse_point_3(&line[1..]).map_err(|err| line.locate(err))
+    }
+
+    fn parse_scalar(line: &ObjLine) -> ParseResult<f32> {
+        let token = line.get(1).ok_or_else(|| line.error("Expected a value"))?;
+        parse_f32(token).map_err(|err| line.locate(err))
+    }
+
+    /// Texture statements may carry options like `-bm 1.0`, the file name goes last.
+    fn parse_map(line: &ObjLine) -> ParseResult<String> {
+        if line.len() < 2 {
+            return Err(line.error("Expected a texture file name"));
+        }
+        Ok(line[line.len() - 1].to_string())
+    }
+}
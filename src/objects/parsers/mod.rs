@@ -1,57 +1,198 @@
-use crate::geometry::{transform::RawTransform, Vector2, Vector3};
+use std::ops::Deref;
+
+use crate::geometry::{transform::RawTransform, Quaternion, Vector2, Vector3};
+
+pub use self::error::{ParseError, ParseResult};
 
 pub mod animation;
+pub mod binary;
+pub mod bvh;
+pub mod error;
+pub mod gltf;
+pub mod material;
+pub mod normals;
+pub mod ply;
+pub mod polygon;
 pub mod shape;
 pub mod skeleton;
 pub mod skinning;
+pub mod stl;
+pub mod validation;
 
 pub fn parse_point_3(split: &[&str]) -> Vector3 {
+    try_parse_point_3(split).unwrap_or_else(|err| panic!("{}", err))
+}
+
+pub fn try_parse_point_3(split: &[&str]) -> ParseResult<Vector3> {
     if split.len() < 3 {
-        panic!("Not enough coordinates for a point 3");
+        return Err(ParseError::new("Not enough coordinates for a point 3"));
     }
 
     let mut coords = [0.0; 3];
     for i in 0..3 {
-        coords[i] = split[i].parse().unwrap();
+        coords[i] = parse_f32(split[i])?;
     }
 
-    Vector3::from_xyz(coords[0], coords[1], coords[2])
+    Ok(Vector3::from_xyz(coords[0], coords[1], coords[2]))
 }
 
 pub fn parse_point_2(split: &[&str]) -> Vector2 {
+    try_parse_point_2(split).unwrap_or_else(|err| panic!("{}", err))
+}
+
+pub fn try_parse_point_2(split: &[&str]) -> ParseResult<Vector2> {
     if split.len() < 2 {
-        panic!("Not enough coordinates for a point 3");
+        return Err(ParseError::new("Not enough coordinates for a point 2"));
     }
 
     let mut coords = [0.0; 2];
     for i in 0..2 {
-        coords[i] = split[i].parse().unwrap();
+        coords[i] = parse_f32(split[i])?;
     }
 
-    Vector2::from_xy(coords[0], coords[1])
+    Ok(Vector2::from_xy(coords[0], coords[1]))
 }
 
 pub fn parse_transform(data: &[&str]) -> RawTransform {
+    try_parse_transform(data).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Euler angles, optionally followed by a translation and a scale.
+pub fn try_parse_transform(data: &[&str]) -> ParseResult<RawTransform> {
     if data.len() < 3 || data.len() % 3 != 0 {
-        panic!("Incorrect transform for a bone");
+        return Err(ParseError::new("Incorrect transform for a bone"));
     }
 
-    let angles = parse_point_3(&data[0..3]);
+    let angles = try_parse_point_3(&data[0..3])?;
     let translate = if data.len() > 3 {
-        parse_point_3(&data[3..6])
+        try_parse_point_3(&data[3..6])?
     } else {
         Vector3::from_xyz(0.0, 0.0, 0.0)
     };
 
     let mut t = RawTransform::new();
-    t.rotate(angles);
+    t.rotation = Quaternion::from_euler(angles);
     t.translate_vec(translate);
-    t
+    if data.len() > 6 {
+        t.scale = try_parse_point_3(&data[6..9])?;
+    }
+    Ok(t)
+}
+
+pub fn parse_quaternion_transform(data: &[&str]) -> RawTransform {
+    try_parse_quaternion_transform(data).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Rotation quaternion and translation, optionally followed by a scale.
+pub fn try_parse_quaternion_transform(data: &[&str]) -> ParseResult<RawTransform> {
+    if data.len() != 7 && data.len() != 10 {
+        return Err(ParseError::new("Incorrect quaternion transform for a bone"));
+    }
+
+    let mut coords = [0.0; 4];
+    for i in 0..4 {
+        coords[i] = parse_f32(data[i])?;
+    }
+    let rotation = Quaternion::from(coords);
+    let length = rotation.length();
+    if length == 0.0 {
+        return Err(ParseError::at_token(data[0], "Rotation quaternion is zero"));
+    }
+
+    let mut t = RawTransform::new();
+    // Written quaternions are unit already, normalizing again could change their last bits
+    t.rotation = if (length - 1.0).abs() > 1e-5 {
+        rotation.normalized()
+    } else {
+        rotation
+    };
+    t.position = try_parse_point_3(&data[4..7])?;
+    if data.len() > 7 {
+        t.scale = try_parse_point_3(&data[7..10])?;
+    }
+    Ok(t)
+}
+
+/// Shortest text that parses back to the same point.
+pub fn write_point_3(point: Vector3) -> String {
+    format!("{} {} {}", point.x(), point.y(), point.z())
+}
+
+/// Counterpart of `parse_quaternion_transform`, parses back to the same transform.
+/// The scale is left out when it is one.
+pub fn write_transform(transform: &RawTransform) -> String {
+    let rotation = transform.rotation;
+    let mut text = format!(
+        "{} {} {} {} {}",
+        rotation.x,
+        rotation.y,
+        rotation.z,
+        rotation.w,
+        write_point_3(transform.position)
+    );
+    if transform.scale.vector != Vector3::repeat(1.0).vector {
+        text = format!("{} {}", text, write_point_3(transform.scale));
+    }
+    text
+}
+
+pub fn parse_f32(token: &str) -> ParseResult<f32> {
+    token
+        .parse()
+        .map_err(|_| ParseError::at_token(token, "Expected a number"))
+}
+
+pub fn parse_f64(token: &str) -> ParseResult<f64> {
+    token
+        .parse()
+        .map_err(|_| ParseError::at_token(token, "Expected a number"))
+}
+
+pub fn parse_usize(token: &str) -> ParseResult<usize> {
+    token
+        .parse()
+        .map_err(|_| ParseError::at_token(token, "Expected a non-negative integer"))
+}
+
+pub struct ObjLine<'a> {
+    pub number: usize,
+    pub text: &'a str,
+    pub split: Vec<&'a str>,
+}
+
+impl<'a> ObjLine<'a> {
+    /// Fills in the line number and the column of the offending token.
+    pub fn locate(&self, error: ParseError) -> ParseError {
+        let column = error.token.as_ref().and_then(|token| {
+            self.split
+                .iter()
+                .find(|it| *it == token)
+                .map(|it| it.as_ptr() as usize - self.text.as_ptr() as usize + 1)
+        });
+        error.with_line(self.number, column)
+    }
+
+    pub fn error(&self, reason: impl Into<String>) -> ParseError {
+        self.locate(ParseError::new(reason))
+    }
+}
+
+impl<'a> Deref for ObjLine<'a> {
+    type Target = [&'a str];
+
+    fn deref(&self) -> &Self::Target {
+        &self.split
+    }
 }
 
-pub fn obj_lines(file: &str) -> impl Iterator<Item = Vec<&str>> {
+pub fn obj_lines(file: &str) -> impl Iterator<Item = ObjLine<'_>> {
     file.lines()
-        .filter(|it| !it.is_empty() && !it.starts_with('#'))
-        .map(|it| it.split(' ').filter(|s| !s.is_empty()).collect())
-        .filter(|it: &Vec<&str>| !it.is_empty())
+        .enumerate()
+        .filter(|(_, it)| !it.is_empty() && !it.starts_with('#'))
+        .map(|(i, it)| ObjLine {
+            number: i + 1,
+            text: it,
+            split: it.split_whitespace().collect(),
+        })
+        .filter(|it| !it.split.is_empty())
 }
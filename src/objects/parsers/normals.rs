@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use crate::geometry::Vector3;
+
+use super::shape::VertexData;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalWeighting {
+    /// Larger faces pull the normal harder
+    Area,
+    /// Each face contributes by the angle of its corner at the vertex
+    Angle,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SmoothingOptions {
+    /// Faces meeting at a sharper angle than this (in radians) stay separated
+    pub crease_angle: f32,
+    pub weighting: NormalWeighting,
+}
+
+impl Default for SmoothingOptions {
+    fn default() -> Self {
+        Self {
+            crease_angle: std::f32::consts::PI,
+            weighting: NormalWeighting::Angle,
+        }
+    }
+}
+
+/// Smooths normals of a triangle list as if every face was in the same smoothing group.
+pub fn smooth_normals(vertices: &mut [VertexData], options: &SmoothingOptions) {
+    let groups = vec![Some(1); vertices.len() / 3];
+    smooth_normals_by_group(vertices, &groups, options);
+}
+
+/// Replaces normals of a triangle list, `groups` holds one entry per triangle:
+/// `None` keeps the triangle normals as they are, `Some(0)` makes it flat shaded
+/// and other values are smoothing groups whose faces share vertex normals.
+pub fn smooth_normals_by_group(
+    vertices: &mut [VertexData],
+    groups: &[Option<u32>],
+    options: &SmoothingOptions,
+) {
+    let triangles = vertices.len() / 3;
+    let face_normals: Vec<Vector3> = (0..triangles)
+        .map(|t| {
+            let [a, b, c] = corners(vertices, t);
+            (b - a).cross(c - a)
+        })
+        .collect();
+    let unit_normals: Vec<Vector3> = face_normals.iter().map(|it| normalize(*it)).collect();
+
+    let mut shared: HashMap<([u32; 3], u32), Vec<usize>> = HashMap::new();
+    for (idx, vertex) in vertices.iter().enumerate() {
+        if let Some(group) = groups[idx / 3].filter(|it| *it != 0) {
+            shared
+                .entry((position_key(vertex.point), group))
+                .or_default()
+                .push(idx);
+        }
+    }
+
+    let min_cos = options.crease_angle.cos();
+    let mut normals: Vec<Option<Vector3>> = vec![None; vertices.len()];
+    for (idx, normal) in normals.iter_mut().enumerate() {
+        let triangle = idx / 3;
+        match groups[triangle] {
+            None => continue,
+            Some(0) => *normal = Some(unit_normals[triangle]),
+            Some(group) => {
+                let own = unit_normals[triangle];
+                let mut sum = Vector3::zero();
+                for &other in shared[&(position_key(vertices[idx].point), group)].iter() {
+                    let other_triangle = other / 3;
+                    if own.dot(unit_normals[other_triangle]) < min_cos {
+                        continue;
+                    }
+                    let weight = match options.weighting {
+                        NormalWeighting::Area => face_normals[other_triangle].length(),
+                        NormalWeighting::Angle => corner_angle(vertices, other),
+                    };
+                    sum += unit_normals[other_triangle] * weight;
+                }
+                *normal = Some(if sum.length() > 0.0 {
+                    normalize(sum)
+                } else {
+                    own
+                });
+            }
+        }
+    }
+
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        if let Some(normal) = normal {
+            vertex.normal = normal;
+        }
+    }
+}
+
+fn corners(vertices: &[VertexData], triangle: usize) -> [Vector3; 3] {
+    [
+        vertices[triangle * 3].point,
+        vertices[triangle * 3 + 1].point,
+        vertices[triangle * 3 + 2].point,
+    ]
+}
+
+fn corner_angle(vertices: &[VertexData], idx: usize) -> f32 {
+    let triangle = idx / 3;
+    let corner = idx % 3;
+    let points = corners(vertices, triangle);
+    let a = points[corner];
+    let b = points[(corner + 1) % 3] - a;
+    let c = points[(corner + 2) % 3] - a;
+    let len = b.length() * c.length();
+    if len == 0.0 {
+        0.0
+    } else {
+        (b.dot(c) / len).clamp(-1.0, 1.0).acos()
+    }
+}
+
+fn normalize(vector: Vector3) -> Vector3 {
+    if vector.length() > 0.0 {
+        vector.normalized()
+    } else {
+        vector
+    }
+}
+
+/// Positive and negative zero should land in the same bucket
+fn position_key(point: Vector3) -> [u32; 3] {
+    [
+        (point.x() + 0.0).to_bits(),
+        (point.y() + 0.0).to_bits(),
+        (point.z() + 0.0).to_bits(),
+    ]
+}
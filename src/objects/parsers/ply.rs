@@ -0,0 +1,479 @@
+use crate::geometry::{Vector2, Vector3};
+
+use super::{
+    normals::SmoothingOptions,
+    obj_lines,
+    shape::{ObjMesh, RawVertexData, VertexData},
+    ObjLine, ParseError, ParseResult,
+};
+
+/// Reads PLY files in the ASCII and both binary encodings into the same triangle lists
+/// `ObjParser` produces.
+///
+/// Vertices may carry normals, texture coordinates (`u v`, `s t` or `texture_u texture_v`)
+/// and `red green blue` colors. Faces come from the `vertex_indices` list of `face` elements,
+/// without normals in the file they are smoothed across shared vertices.
+pub struct PlyParser;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Where the values of the element rows come from.
+trait Rows {
+    fn start_row(&mut self) -> ParseResult<()>;
+    fn value(&mut self, scalar: Scalar) -> ParseResult<f64>;
+    fn end_row(&mut self) -> ParseResult<()>;
+}
+
+impl Scalar {
+    fn parse(token: &str) -> ParseResult<Self> {
+        Ok(match token {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => return Err(ParseError::at_token(token, "Unknown property type")),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    /// Colors stored as integers use the whole range of the type.
+    fn color_scale(self) -> f64 {
+        match self {
+            Self::U8 => u8::MAX as f64,
+            Self::U16 => u16::MAX as f64,
+            Self::U32 => u32::MAX as f64,
+            Self::I8 => i8::MAX as f64,
+            Self::I16 => i16::MAX as f64,
+            Self::I32 => i32::MAX as f64,
+            Self::F32 | Self::F64 => 1.0,
+        }
+    }
+}
+
+impl Element {
+    fn scalar(&self, names: &[&str]) -> Option<(usize, Scalar)> {
+        self.properties
+            .iter()
+            .filter_map(|it| match it {
+                Property::Scalar(name, scalar) => Some((name, *scalar)),
+                Property::List(..) => None,
+            })
+            .enumerate()
+            .find(|(_, (name, _))| names.contains(&name.as_str()))
+            .map(|(idx, (_, scalar))| (idx, scalar))
+    }
+
+    fn list(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .filter_map(|it| match it {
+                Property::List(name, ..) => Some(name),
+                Property::Scalar(..) => None,
+            })
+            .position(|name| names.contains(&name.as_str()))
+    }
+
+    /// Scalar properties go to `scalars` and lists to `lists`, both in the order of the header.
+    fn read_row(
+        &self,
+        rows: &mut impl Rows,
+        scalars: &mut Vec<f64>,
+        lists: &mut Vec<Vec<f64>>,
+    ) -> ParseResult<()> {
+        scalars.clear();
+        lists.clear();
+        rows.start_row()?;
+        for property in self.properties.iter() {
+            match property {
+                Property::Scalar(_, scalar) => scalars.push(rows.value(*scalar)?),
+                Property::List(_, count, item) => {
+                    let count = rows.value(*count)?;
+                    if count < 0.0 || count.fract() != 0.0 {
+                        return Err(ParseError::new(format!(
+                            "List length {} is not a non-negative integer",
+                            count
+                        )));
+                    }
+                    let list = (0..count as usize)
+                        .map(|_| rows.value(*item))
+                        .collect::<ParseResult<_>>()?;
+                    lists.push(list);
+                }
+            }
+        }
+        rows.end_row()
+    }
+
+    /// Smallest size of a row in the binary encodings, lists may be empty.
+    fn min_size(&self) -> usize {
+        self.properties
+            .iter()
+            .map(|it| match it {
+                Property::Scalar(_, scalar) | Property::List(_, scalar, _) => scalar.size(),
+            })
+            .sum()
+    }
+}
+
+struct AsciiRows<'a, I: Iterator<Item = ObjLine<'a>>> {
+    lines: I,
+    line: Option<ObjLine<'a>>,
+    token: usize,
+}
+
+impl<'a, I: Iterator<Item = ObjLine<'a>>> AsciiRows<'a, I> {
+    fn locate(&self, error: ParseError) -> ParseError {
+        match &self.line {
+            Some(line) => line.locate(error),
+            None => error,
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = ObjLine<'a>>> Rows for AsciiRows<'a, I> {
+    fn start_row(&mut self) -> ParseResult<()> {
+        self.line =
+            Some(self.lines.next().ok_or_else(|| {
+                ParseError::new("PLY file has fewer rows than its header declares")
+            })?);
+        self.token = 0;
+        Ok(())
+    }
+
+    fn value(&mut self, _: Scalar) -> ParseResult<f64> {
+        let line = self.line.as_ref().unwrap();
+        let token = line
+            .get(self.token)
+            .ok_or_else(|| line.error("Row has fewer values than its element declares"))?;
+        self.token += 1;
+        token
+            .parse()
+            .map_err(|_| line.locate(ParseError::at_token(token, "Expected a number")))
+    }
+
+    fn end_row(&mut self) -> ParseResult<()> {
+        let line = self.line.as_ref().unwrap();
+        if self.token != line.len() {
+            return Err(self.locate(ParseError::new(
+                "Row has more values than its element declares",
+            )));
+        }
+        Ok(())
+    }
+}
+
+struct BinaryRows<'a> {
+    data: &'a [u8],
+    offset: usize,
+    big_endian: bool,
+}
+
+impl<'a> Rows for BinaryRows<'a> {
+    fn start_row(&mut self) -> ParseResult<()> {
+        Ok(())
+    }
+
+    fn value(&mut self, scalar: Scalar) -> ParseResult<f64> {
+        let size = scalar.size();
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + size)
+            .ok_or_else(|| ParseError::new("Unexpected end of PLY file"))?;
+        self.offset += size;
+
+        let mut buffer = [0; 8];
+        buffer[..size].copy_from_slice(bytes);
+        if self.big_endian {
+            buffer[..size].reverse();
+        }
+        let [a, b, c, d, ..] = buffer;
+        Ok(match scalar {
+            Scalar::I8 => a as i8 as f64,
+            Scalar::U8 => a as f64,
+            Scalar::I16 => i16::from_le_bytes([a, b]) as f64,
+            Scalar::U16 => u16::from_le_bytes([a, b]) as f64,
+            Scalar::I32 => i32::from_le_bytes([a, b, c, d]) as f64,
+            Scalar::U32 => u32::from_le_bytes([a, b, c, d]) as f64,
+            Scalar::F32 => f32::from_le_bytes([a, b, c, d]) as f64,
+            Scalar::F64 => f64::from_le_bytes(buffer),
+        })
+    }
+
+    fn end_row(&mut self) -> ParseResult<()> {
+        Ok(())
+    }
+}
+
+impl PlyParser {
+    pub fn parse(data: &[u8]) -> Vec<VertexData> {
+        Self::try_parse(data).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse(data: &[u8]) -> ParseResult<Vec<VertexData>> {
+        Ok(Self::try_parse_mesh(data)?.vertices)
+    }
+
+    pub fn parse_mesh(data: &[u8]) -> ObjMesh {
+        Self::try_parse_mesh(data).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// The mesh is named `default`, its `points` are the vertices of the file.
+    pub fn try_parse_mesh(data: &[u8]) -> ParseResult<ObjMesh> {
+        Self::try_parse_mesh_with_options(data, &SmoothingOptions::default())
+    }
+
+    pub fn parse_mesh_with_options(data: &[u8], options: &SmoothingOptions) -> ObjMesh {
+        Self::try_parse_mesh_with_options(data, options).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse_mesh_with_options(
+        data: &[u8],
+        options: &SmoothingOptions,
+    ) -> ParseResult<ObjMesh> {
+        let end = Self::header_end(data)?;
+        let header = std::str::from_utf8(&data[..end])
+            .map_err(|_| ParseError::new("PLY header is not valid UTF-8"))?;
+        let (format, elements) = Self::parse_header(header)?;
+
+        if format == Format::Ascii {
+            let body = std::str::from_utf8(&data[end..])
+                .map_err(|_| ParseError::new("ASCII PLY file is not valid UTF-8"))?;
+            let header_lines = header.lines().count();
+            let lines = obj_lines(body).map(|mut it| {
+                it.number += header_lines;
+                it
+            });
+            let mut rows = AsciiRows {
+                lines,
+                line: None,
+                token: 0,
+            };
+            Self::read_mesh(&elements, &mut rows, options).map_err(|err| rows.locate(err))
+        } else {
+            let size: usize = elements.iter().map(|it| it.count * it.min_size()).sum();
+            if size > data.len() - end {
+                return Err(ParseError::new(
+                    "PLY file is shorter than its header declares",
+                ));
+            }
+            let mut rows = BinaryRows {
+                data: &data[end..],
+                offset: 0,
+                big_endian: format == Format::BigEndian,
+            };
+            Self::read_mesh(&elements, &mut rows, options)
+        }
+    }
+
+    /// Offset of the first byte after the `end_header` line.
+    fn header_end(data: &[u8]) -> ParseResult<usize> {
+        if !data.starts_with(b"ply") {
+            return Err(ParseError::new("Not a PLY file"));
+        }
+        let marker = b"end_header";
+        let start = data
+            .windows(marker.len())
+            .position(|it| it == marker)
+            .ok_or_else(|| ParseError::new("PLY file has no end_header"))?;
+        let newline = data[start..]
+            .iter()
+            .position(|it| *it == b'\n')
+            .ok_or_else(|| ParseError::new("PLY file has no data after its header"))?;
+        Ok(start + newline + 1)
+    }
+
+    fn parse_header(header: &str) -> ParseResult<(Format, Vec<Element>)> {
+        let mut format = None;
+        let mut elements: Vec<Element> = vec![];
+        for line in obj_lines(header) {
+            let result = match line[0] {
+                "ply" | "comment" | "obj_info" | "end_header" => continue,
+                "format" => Self::parse_format(&line[1..]).map(|it| format = Some(it)),
+                "element" => Self::parse_element(&line[1..]).map(|it| elements.push(it)),
+                "property" => match elements.last_mut() {
+                    Some(element) => {
+                        Self::parse_property(&line[1..]).map(|it| element.properties.push(it))
+                    }
+                    None => Err(ParseError::new("Property outside of an element")),
+                },
+                _ => Err(ParseError::at_token(line[0], "Unknown keyword")),
+            };
+            result.map_err(|err| line.locate(err))?;
+        }
+        let format = format.ok_or_else(|| ParseError::new("PLY header has no format"))?;
+        Ok((format, elements))
+    }
+
+    fn parse_format(split: &[&str]) -> ParseResult<Format> {
+        match split.first() {
+            Some(&"ascii") => Ok(Format::Ascii),
+            Some(&"binary_little_endian") => Ok(Format::LittleEndian),
+            Some(&"binary_big_endian") => Ok(Format::BigEndian),
+            Some(token) => Err(ParseError::at_token(token, "Unknown PLY format")),
+            None => Err(ParseError::new("Expected a PLY format")),
+        }
+    }
+
+    fn parse_element(split: &[&str]) -> ParseResult<Element> {
+        if split.len() < 2 {
+            return Err(ParseError::new("Expected an element name and count"));
+        }
+        let count = split[1]
+            .parse()
+            .map_err(|_| ParseError::at_token(split[1], "Expected a non-negative integer"))?;
+        Ok(Element {
+            name: split[0].to_string(),
+            count,
+            properties: vec![],
+        })
+    }
+
+    fn parse_property(split: &[&str]) -> ParseResult<Property> {
+        match split {
+            ["list", count, item, name] => Ok(Property::List(
+                name.to_string(),
+                Scalar::parse(count)?,
+                Scalar::parse(item)?,
+            )),
+            [scalar, name] if *scalar != "list" => {
+                Ok(Property::Scalar(name.to_string(), Scalar::parse(scalar)?))
+            }
+            _ => Err(ParseError::new("Expected a property type and name")),
+        }
+    }
+
+    fn read_mesh(
+        elements: &[Element],
+        rows: &mut impl Rows,
+        options: &SmoothingOptions,
+    ) -> ParseResult<ObjMesh> {
+        let mut mesh = ObjMesh::new("default");
+        let mut vertices = vec![];
+        let (mut scalars, mut lists) = (vec![], vec![]);
+
+        for element in elements.iter() {
+            match element.name.as_str() {
+                "vertex" => {
+                    for _ in 0..element.count {
+                        element.read_row(rows, &mut scalars, &mut lists)?;
+                        vertices.push(Self::vertex(element, &scalars)?);
+                    }
+                }
+                "face" => {
+                    let list = element
+                        .list(&["vertex_indices", "vertex_index"])
+                        .ok_or_else(|| ParseError::new("Faces have no vertex_indices"))?;
+                    for _ in 0..element.count {
+                        element.read_row(rows, &mut scalars, &mut lists)?;
+                        let polygon = Self::polygon(&lists[list], &vertices)?;
+                        // Without normals in the file the surface is smooth across shared vertices
+                        mesh.push_polygon(polygon, &None, 1);
+                    }
+                }
+                _ => {
+                    for _ in 0..element.count {
+                        element.read_row(rows, &mut scalars, &mut lists)?;
+                    }
+                }
+            }
+        }
+
+        mesh.points = vertices.len();
+        mesh.smooth_normals(options);
+        Ok(mesh)
+    }
+
+    fn vertex(element: &Element, scalars: &[f64]) -> ParseResult<RawVertexData> {
+        let value = |names: &[&str]| element.scalar(names).map(|(idx, _)| scalars[idx] as f32);
+        let vector = |names: [&[&str]; 3]| match names.map(value) {
+            [Some(x), Some(y), Some(z)] => Some(Vector3::from_xyz(x, y, z)),
+            _ => None,
+        };
+
+        let point = vector([&["x"], &["y"], &["z"]])
+            .ok_or_else(|| ParseError::new("Vertices have no x, y and z"))?;
+        let mut vertex = RawVertexData::new(point);
+        vertex.normal = vector([&["nx"], &["ny"], &["nz"]]);
+        // Texture space is flipped the same way as for OBJ files
+        vertex.texture_coord = match (
+            value(&["u", "s", "texture_u", "texture_s"]),
+            value(&["v", "t", "texture_v", "texture_t"]),
+        ) {
+            (Some(u), Some(v)) => Some(Vector2::from_xy(u, 1.0 - v)),
+            _ => None,
+        };
+
+        let channel = |names: &[&str]| {
+            element
+                .scalar(names)
+                .map(|(idx, scalar)| (scalars[idx] / scalar.color_scale()) as f32)
+        };
+        if let [Some(r), Some(g), Some(b)] = [
+            channel(&["red", "diffuse_red"]),
+            channel(&["green", "diffuse_green"]),
+            channel(&["blue", "diffuse_blue"]),
+        ] {
+            vertex.color = Vector3::from_xyz(r, g, b);
+        }
+        Ok(vertex)
+    }
+
+    fn polygon(indices: &[f64], vertices: &[RawVertexData]) -> ParseResult<Vec<RawVertexData>> {
+        if indices.len() < 3 {
+            return Err(ParseError::new("Face has fewer than 3 vertices"));
+        }
+        indices
+            .iter()
+            .map(|idx| {
+                let vertex = if *idx < 0.0 {
+                    None
+                } else {
+                    vertices.get(*idx as usize)
+                };
+                vertex
+                    .copied()
+                    .ok_or_else(|| ParseError::new(format!("Vertex index {} is out of range", idx)))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,108 @@
+use crate::geometry::{Vector2, Vector3};
+
+/// Normal of an arbitrary (possibly non-planar or concave) polygon by Newell's method.
+pub fn polygon_normal(points: &[Vector3]) -> Vector3 {
+    let mut normal = Vector3::zero();
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        normal += Vector3::from_xyz(
+            (a.y() - b.y()) * (a.z() + b.z()),
+            (a.z() - b.z()) * (a.x() + b.x()),
+            (a.x() - b.x()) * (a.y() + b.y()),
+        );
+    }
+    normal
+}
+
+/// Two coordinate axes of the plane the polygon is most aligned with.
+pub fn dominant_axes(normal: Vector3) -> (usize, usize) {
+    let (x, y, z) = (normal.x().abs(), normal.y().abs(), normal.z().abs());
+    if x >= y && x >= z {
+        (1, 2)
+    } else if y >= z {
+        (2, 0)
+    } else {
+        (0, 1)
+    }
+}
+
+pub fn project(point: Vector3, axes: (usize, usize)) -> Vector2 {
+    Vector2::from_xy(point.get(axes.0), point.get(axes.1))
+}
+
+/// Splits a polygon into triangles by ear clipping, keeping the original winding.
+/// Returns indices into `points`.
+pub fn triangulate(points: &[Vector3]) -> Vec<[usize; 3]> {
+    if points.len() < 3 {
+        return vec![];
+    }
+    if points.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    let axes = dominant_axes(polygon_normal(points));
+    let flat: Vec<Vector2> = points.iter().map(|it| project(*it, axes)).collect();
+    let orientation = signed_area(&flat).signum();
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let a = remaining[(i + n - 1) % n];
+            let b = remaining[i];
+            let c = remaining[(i + 1) % n];
+            is_ear(&flat, &remaining, [a, b, c], orientation)
+        });
+
+        match ear {
+            Some(i) => {
+                triangles.push([
+                    remaining[(i + n - 1) % n],
+                    remaining[i],
+                    remaining[(i + 1) % n],
+                ]);
+                remaining.remove(i);
+            }
+            // Degenerate or self-intersecting polygon, fall back to a fan
+            None => break,
+        }
+    }
+
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+
+    triangles
+}
+
+fn signed_area(points: &[Vector2]) -> f32 {
+    let mut area = 0.0;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        area += a.x() * b.y() - b.x() * a.y();
+    }
+    area * 0.5
+}
+
+fn cross(o: Vector2, a: Vector2, b: Vector2) -> f32 {
+    (a.x() - o.x()) * (b.y() - o.y()) - (a.y() - o.y()) * (b.x() - o.x())
+}
+
+fn is_ear(flat: &[Vector2], remaining: &[usize], [a, b, c]: [usize; 3], orientation: f32) -> bool {
+    let (pa, pb, pc) = (flat[a], flat[b], flat[c]);
+    if cross(pa, pb, pc) * orientation <= 0.0 {
+        return false;
+    }
+
+    !remaining
+        .iter()
+        .filter(|&&it| it != a && it != b && it != c)
+        .any(|&it| {
+            let p = flat[it];
+            cross(pa, pb, p) * orientation >= 0.0
+                && cross(pb, pc, p) * orientation >= 0.0
+                && cross(pc, pa, p) * orientation >= 0.0
+        })
+}
@@ -1,129 +1,764 @@
+use std::{collections::HashMap, fmt::Write, ops::Index};
+
 use crate::geometry::{vector::Vector4, Vector2, Vector3};
 
-use super::{obj_lines, parse_point_2, parse_point_3, skinning::Skinning};
+use super::{
+    normals::{smooth_normals_by_group, SmoothingOptions},
+    obj_lines, parse_f32, parse_f64, parse_usize,
+    polygon::{dominant_axes, polygon_normal, project, triangulate},
+    skinning::{Skinning, SkinningVertex},
+    try_parse_point_3, write_point_3, ParseError, ParseResult,
+};
+
+const DEFAULT_NAME: &str = "default";
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct VertexData {
     pub point: Vector3,
     pub normal: Vector3,
     pub texture_coord: Vector2,
     pub bones: Vector4,
     pub weights: Vector4,
+    /// Multiplies the texture color, white for formats without vertex colors
+    pub color: Vector3,
+}
+
+impl VertexData {
+    /// Color of vertices that come without one, leaves the texture as it is.
+    pub fn default_color() -> Vector3 {
+        Vector3::from_xyz(1.0, 1.0, 1.0)
+    }
+
+    /// Bit pattern of all attributes, what belongs to an OBJ `v` statement goes first.
+    fn key(&self) -> [u32; 19] {
+        let (point, bones, weights, color) = (self.point, self.bones, self.weights, self.color);
+        [
+            point.x(),
+            point.y(),
+            point.z(),
+            bones.x(),
+            bones.y(),
+            bones.z(),
+            bones.w(),
+            weights.x(),
+            weights.y(),
+            weights.z(),
+            weights.w(),
+            color.x(),
+            color.y(),
+            color.z(),
+            self.normal.x(),
+            self.normal.y(),
+            self.normal.z(),
+            self.texture_coord.x(),
+            self.texture_coord.y(),
+        ]
+        .map(f32::to_bits)
+    }
+}
+
+/// A range of triangle vertices drawn with one material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubMesh {
+    pub material: Option<String>,
+    pub start: usize,
+    pub count: usize,
+}
+
+pub struct ObjMesh {
+    pub name: String,
+    pub vertices: Vec<VertexData>,
+    pub submeshes: Vec<SubMesh>,
+    pub material_libraries: Vec<String>,
+    /// Smoothing group of every triangle, `None` for faces with normals from the file
+    pub smoothing_groups: Vec<Option<u32>>,
+    /// Number of `v` positions in the file, a `.skin` file has one entry for each of them
+    pub points: usize,
+}
+
+impl ObjMesh {
+    pub(super) fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            vertices: vec![],
+            submeshes: vec![],
+            material_libraries: vec![],
+            smoothing_groups: vec![],
+            points: 0,
+        }
+    }
+
+    pub(super) fn push_triangle(
+        &mut self,
+        triangle: [VertexData; 3],
+        material: &Option<String>,
+        smoothing_group: Option<u32>,
+    ) {
+        match self.submeshes.last_mut() {
+            Some(last) if last.material == *material => last.count += 3,
+            _ => self.submeshes.push(SubMesh {
+                material: material.clone(),
+                start: self.vertices.len(),
+                count: 3,
+            }),
+        }
+        self.vertices.extend(triangle);
+        self.smoothing_groups.push(smoothing_group);
+    }
+
+    /// Triangulates a polygon, faces without normals get them from `smoothing_group`
+    /// once the whole mesh is known.
+    pub(super) fn push_polygon(
+        &mut self,
+        mut vertices: Vec<RawVertexData>,
+        material: &Option<String>,
+        smoothing_group: u32,
+    ) {
+        let points: Vec<Vector3> = vertices.iter().map(|it| it.point).collect();
+
+        // Planar projection for faces exported without texture coordinates
+        if vertices.iter().any(|it| it.texture_coord.is_none()) {
+            let axes = dominant_axes(polygon_normal(&points));
+            for vertex in vertices.iter_mut() {
+                *vertex = vertex.with_default_texture_coord(project(vertex.point, axes));
+            }
+        }
+
+        let smoothing_group = if vertices.iter().any(|it| it.normal.is_none()) {
+            Some(smoothing_group)
+        } else {
+            None
+        };
+        for [a, b, c] in triangulate(&points) {
+            let triangle = [vertices[a], vertices[b], vertices[c]]
+                .map(|it| it.with_default_normal(Vector3::zero()).unwrap());
+            self.push_triangle(triangle, material, smoothing_group);
+        }
+    }
+
+    /// Regenerates normals of faces that came without them, e.g. with another crease angle.
+    pub fn smooth_normals(&mut self, options: &SmoothingOptions) {
+        smooth_normals_by_group(&mut self.vertices, &self.smoothing_groups, options);
+    }
+
+    /// Joins several meshes into one, e.g. to render a whole file as a single shape.
+    pub fn merge(name: &str, meshes: Vec<ObjMesh>) -> Self {
+        let mut merged = Self::new(name);
+        for mesh in meshes {
+            merged.points = merged.points.max(mesh.points);
+            for submesh in mesh.submeshes {
+                for t in (submesh.start / 3)..((submesh.start + submesh.count) / 3) {
+                    let triangle = [
+                        mesh.vertices[t * 3].clone(),
+                        mesh.vertices[t * 3 + 1].clone(),
+                        mesh.vertices[t * 3 + 2].clone(),
+                    ];
+                    merged.push_triangle(triangle, &submesh.material, mesh.smoothing_groups[t]);
+                }
+            }
+            for library in mesh.material_libraries {
+                if !merged.material_libraries.contains(&library) {
+                    merged.material_libraries.push(library);
+                }
+            }
+        }
+        merged
+    }
+}
+
+/// Mesh with shared vertices, submesh ranges refer to `indices`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedMesh {
+    pub name: String,
+    pub vertices: Vec<VertexData>,
+    pub indices: Vec<u32>,
+    pub submeshes: Vec<SubMesh>,
+}
+
+impl IndexedMesh {
+    /// Merges triangle corners that are equal in every attribute.
+    pub fn new(name: &str, triangles: &[VertexData], submeshes: Vec<SubMesh>) -> Self {
+        let mut vertices = vec![];
+        let mut indices = Vec::with_capacity(triangles.len());
+        let mut known = HashMap::new();
+
+        for vertex in triangles.iter() {
+            let idx = *known.entry(vertex.key()).or_insert_with(|| {
+                vertices.push(vertex.clone());
+                vertices.len() as u32 - 1
+            });
+            indices.push(idx);
+        }
+
+        Self {
+            name: name.to_string(),
+            vertices,
+            indices,
+            submeshes,
+        }
+    }
+
+    pub fn from_mesh(mesh: &ObjMesh) -> Self {
+        Self::new(&mesh.name, &mesh.vertices, mesh.submeshes.clone())
+    }
+
+    /// Expands the mesh back into a triangle list.
+    pub fn triangles(&self) -> Vec<VertexData> {
+        self.indices
+            .iter()
+            .map(|it| self.vertices[*it as usize].clone())
+            .collect()
+    }
+}
+
+/// Meshes of a file split by `o` and `g` statements. A mesh is named after its object,
+/// its group, or `object/group` when both are present; faces outside of any are `default`.
+pub struct ObjMeshes {
+    pub meshes: Vec<ObjMesh>,
+    pub names: HashMap<String, usize>,
+}
+
+impl ObjMeshes {
+    pub fn get(&self, name: &str) -> Option<&ObjMesh> {
+        self.names.get(name).map(|idx| &self.meshes[*idx])
+    }
+}
+
+impl Index<&str> for ObjMeshes {
+    type Output = ObjMesh;
+
+    fn index(&self, name: &str) -> &Self::Output {
+        &self.meshes[self.names[name]]
+    }
 }
 
 pub struct ObjParser {
     points: Vec<Vector3>,
+    colors: Vec<Vector3>,
     texture_coords: Vec<Vector2>,
     normals: Vec<Vector3>,
-    vertices: Vec<VertexData>,
+    meshes: Vec<ObjMesh>,
+    names: HashMap<String, usize>,
+    current_mesh: Option<usize>,
+    object: Option<String>,
+    group: Option<String>,
+    material: Option<String>,
+    smoothing_group: u32,
+    material_libraries: Vec<String>,
 }
 
+/// Vertex as read from a file, missing attributes are filled in per polygon.
 #[derive(Clone, Copy)]
-struct RawVertexData {
+pub(super) struct RawVertexData {
     pub point: Vector3,
     pub normal: Option<Vector3>,
-    pub texture_coord: Vector2,
+    pub texture_coord: Option<Vector2>,
     pub bones: Option<Vector4>,
     pub weights: Option<Vector4>,
+    pub color: Vector3,
 }
 
 impl RawVertexData {
+    pub(super) fn new(point: Vector3) -> Self {
+        Self {
+            point,
+            normal: None,
+            texture_coord: None,
+            bones: None,
+            weights: None,
+            color: VertexData::default_color(),
+        }
+    }
+
     fn unwrap(self) -> VertexData {
         VertexData {
             point: self.point,
-            texture_coord: self.texture_coord,
+            texture_coord: self.texture_coord.unwrap(),
             normal: self.normal.unwrap(),
             bones: self.bones.unwrap_or_default(),
             weights: self.weights.unwrap_or_default(),
+            color: self.color,
         }
     }
 
-    fn with_normals(mut self, normal: Vector3) -> RawVertexData {
-        self.normal = Some(normal);
+    fn with_default_normal(mut self, normal: Vector3) -> RawVertexData {
+        self.normal = self.normal.or(Some(normal));
+        self
+    }
+
+    fn with_default_texture_coord(mut self, texture_coord: Vector2) -> RawVertexData {
+        self.texture_coord = self.texture_coord.or(Some(texture_coord));
         self
     }
 }
 
 impl ObjParser {
     pub fn parse(file: &str) -> Vec<VertexData> {
-        Self::new().parse_obj(file, None)
+        Self::try_parse(file).unwrap_or_else(|err| panic!("{}", err))
     }
 
     pub fn parse_with_skin(file: &str, skin: &Skinning) -> Vec<VertexData> {
-        Self::new().parse_obj(file, Some(skin))
+        Self::try_parse_with_skin(file, skin).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse(file: &str) -> ParseResult<Vec<VertexData>> {
+        Ok(Self::try_parse_mesh(file, None)?.vertices)
+    }
+
+    pub fn try_parse_with_skin(file: &str, skin: &Skinning) -> ParseResult<Vec<VertexData>> {
+        Ok(Self::try_parse_mesh(file, Some(skin))?.vertices)
+    }
+
+    pub fn parse_mesh(file: &str, skin: Option<&Skinning>) -> ObjMesh {
+        Self::try_parse_mesh(file, skin).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse_mesh(file: &str, skin: Option<&Skinning>) -> ParseResult<ObjMesh> {
+        Self::try_parse_mesh_with_options(file, skin, &SmoothingOptions::default())
+    }
+
+    pub fn parse_mesh_with_options(
+        file: &str,
+        skin: Option<&Skinning>,
+        options: &SmoothingOptions,
+    ) -> ObjMesh {
+        Self::try_parse_mesh_with_options(file, skin, options)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse_mesh_with_options(
+        file: &str,
+        skin: Option<&Skinning>,
+        options: &SmoothingOptions,
+    ) -> ParseResult<ObjMesh> {
+        let meshes = Self::try_parse_named_with_options(file, skin, options)?;
+        Ok(ObjMesh::merge(DEFAULT_NAME, meshes.meshes))
+    }
+
+    pub fn parse_named(file: &str, skin: Option<&Skinning>) -> ObjMeshes {
+        Self::try_parse_named(file, skin).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse_named(file: &str, skin: Option<&Skinning>) -> ParseResult<ObjMeshes> {
+        Self::try_parse_named_with_options(file, skin, &SmoothingOptions::default())
+    }
+
+    pub fn parse_named_with_options(
+        file: &str,
+        skin: Option<&Skinning>,
+        options: &SmoothingOptions,
+    ) -> ObjMeshes {
+        Self::try_parse_named_with_options(file, skin, options)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse_named_with_options(
+        file: &str,
+        skin: Option<&Skinning>,
+        options: &SmoothingOptions,
+    ) -> ParseResult<ObjMeshes> {
+        Self::new().parse_obj(file, skin, options)
     }
 
     fn new() -> Self {
         Self {
             points: vec![],
+            colors: vec![],
             texture_coords: vec![],
             normals: vec![],
-            vertices: vec![],
+            meshes: vec![],
+            names: HashMap::new(),
+            current_mesh: None,
+            object: None,
+            group: None,
+            material: None,
+            smoothing_group: 0,
+            material_libraries: vec![],
         }
     }
 
-    fn parse_obj(mut self, file: &str, skinning: Option<&Skinning>) -> Vec<VertexData> {
-        for split in obj_lines(file) {
-            match split[0] {
-                "v" => self.points.push(parse_point_3(&split[1..])),
+    fn parse_obj(
+        mut self,
+        file: &str,
+        skinning: Option<&Skinning>,
+        options: &SmoothingOptions,
+    ) -> ParseResult<ObjMeshes> {
+        for line in obj_lines(file) {
+            let result = match line[0] {
+                "v" => self.parse_point(&line[1..]),
                 "vt" => {
-                    let coord = parse_point_2(&split[1..]);
-                    self.texture_coords
-                        .push(Vector2::from_xy(coord.x(), 1.0 - coord.y()));
+                    Self::parse_texture_coord(&line[1..]).map(|it| self.texture_coords.push(it))
+                }
+                "vn" => try_parse_point_3(&line[1..]).map(|it| self.normals.push(it)),
+                "f" => self.parse_polygon(&line[1..], skinning),
+                "o" => {
+                    self.object = Self::parse_name(&line[1..]);
+                    self.group = None;
+                    self.current_mesh = None;
+                    Ok(())
+                }
+                "g" => {
+                    self.group = Self::parse_name(&line[1..]);
+                    self.current_mesh = None;
+                    Ok(())
+                }
+                "usemtl" => match line.get(1) {
+                    Some(name) => {
+                        self.material = Some(name.to_string());
+                        Ok(())
+                    }
+                    None => Err(ParseError::new("Expected a material name")),
+                },
+                "s" => match line.get(1) {
+                    Some(&"off") => {
+                        self.smoothing_group = 0;
+                        Ok(())
+                    }
+                    Some(group) => parse_usize(group).map(|it| self.smoothing_group = it as u32),
+                    None => Err(ParseError::new("Expected a smoothing group")),
+                },
+                "mtllib" => {
+                    let libraries = line[1..].iter().map(|it| it.to_string());
+                    self.material_libraries.extend(libraries);
+                    Ok(())
                 }
-                "vn" => self.normals.push(parse_point_3(&split[1..])),
-                "f" => self.parse_polygon(&split[1..], skinning),
                 _ => continue,
-            }
+            };
+            result.map_err(|err| line.locate(err))?;
         }
-        self.vertices
+
+        for mesh in self.meshes.iter_mut() {
+            mesh.material_libraries = self.material_libraries.clone();
+            mesh.points = self.points.len();
+            mesh.smooth_normals(options);
+        }
+
+        Ok(ObjMeshes {
+            meshes: self.meshes,
+            names: self.names,
+        })
+    }
+
+    /// Positions may be followed by an `r g b` vertex color in the range 0 to 1.
+    fn parse_point(&mut self, split: &[&str]) -> ParseResult<()> {
+        let point = try_parse_point_3(split)?;
+        let color = if split.len() >= 6 {
+            try_parse_point_3(&split[3..])?
+        } else {
+            VertexData::default_color()
+        };
+        self.points.push(point);
+        self.colors.push(color);
+        Ok(())
     }
 
-    fn parse_polygon(&mut self, split: &[&str], skinning: Option<&Skinning>) {
-        if split.len() < 2 {
-            panic!("Not enough entities for a polygon");
+    /// OBJ puts the origin of texture space in the bottom left corner, WebGL in the top left.
+    /// The flip is done in double precision, so `ObjWriter` can undo it exactly.
+    fn parse_texture_coord(split: &[&str]) -> ParseResult<Vector2> {
+        let u = split
+            .first()
+            .ok_or_else(|| ParseError::new("Not enough coordinates for a texture coordinate"))?;
+        let v = split.get(1).map_or(Ok(0.0), |it| parse_f64(it))?;
+        Ok(Vector2::from_xy(parse_f32(u)?, (1.0 - v) as f32))
+    }
+
+    fn parse_name(split: &[&str]) -> Option<String> {
+        if split.is_empty() {
+            None
+        } else {
+            Some(split.join(" "))
         }
-        let v0 = self.parse_vertex_data(split[0], skinning);
-        let missing_normals = v0.normal.is_none();
+    }
 
-        for i in 1..split.len() - 1 {
-            let v1 = self.parse_vertex_data(split[i], skinning);
-            let v2 = self.parse_vertex_data(split[i + 1], skinning);
-            if !missing_normals {
-                self.vertices.push(v0.unwrap());
-                self.vertices.push(v1.unwrap());
-                self.vertices.push(v2.unwrap());
-            } else {
-                let normal = (v1.point - v0.point).cross(v2.point - v0.point);
-                self.vertices.push(v0.with_normals(normal).unwrap());
-                self.vertices.push(v1.with_normals(normal).unwrap());
-                self.vertices.push(v2.with_normals(normal).unwrap());
+    fn current_mesh(&mut self) -> &mut ObjMesh {
+        let idx = match self.current_mesh {
+            Some(idx) => idx,
+            None => {
+                let name = match (&self.object, &self.group) {
+                    (Some(object), Some(group)) => format!("{}/{}", object, group),
+                    (Some(name), None) | (None, Some(name)) => name.clone(),
+                    (None, None) => DEFAULT_NAME.to_string(),
+                };
+                let meshes = &mut self.meshes;
+                let idx = *self.names.entry(name).or_insert_with_key(|name| {
+                    meshes.push(ObjMesh::new(name));
+                    meshes.len() - 1
+                });
+                self.current_mesh = Some(idx);
+                idx
             }
+        };
+        &mut self.meshes[idx]
+    }
+
+    fn parse_polygon(&mut self, split: &[&str], skinning: Option<&Skinning>) -> ParseResult<()> {
+        if split.len() < 3 {
+            return Err(ParseError::new("Not enough entities for a polygon"));
         }
+        let vertices = split
+            .iter()
+            .map(|it| self.parse_vertex_data(it, skinning))
+            .collect::<ParseResult<Vec<_>>>()?;
+        let material = self.material.clone();
+        let smoothing_group = self.smoothing_group;
+        self.current_mesh()
+            .push_polygon(vertices, &material, smoothing_group);
+        Ok(())
     }
 
-    fn parse_vertex_data(&self, string: &str, skinning: Option<&Skinning>) -> RawVertexData {
+    /// Parses any of `v`, `v/vt`, `v//vn` and `v/vt/vn`, with negative indices counting
+    /// back from the latest element.
+    fn parse_vertex_data(
+        &self,
+        string: &str,
+        skinning: Option<&Skinning>,
+    ) -> ParseResult<RawVertexData> {
         let split: Vec<&str> = string.split('/').collect();
+        if split.len() > 3 {
+            return Err(ParseError::at_token(
+                string,
+                "Too many indices for a vertex",
+            ));
+        }
 
-        let point_idx = split[0].parse::<usize>().unwrap() - 1;
+        let point_idx = Self::parse_index(string, split[0], self.points.len())?;
         let point = self.points[point_idx];
 
-        let texture_idx = split[1].parse::<usize>().unwrap() - 1;
-        let texture = self.texture_coords[texture_idx];
+        let texture_coord = match split.get(1) {
+            Some(idx) if !idx.is_empty() => {
+                let texture_idx = Self::parse_index(string, idx, self.texture_coords.len())?;
+                Some(self.texture_coords[texture_idx])
+            }
+            _ => None,
+        };
+
+        let normal = match split.get(2) {
+            Some(idx) if !idx.is_empty() => {
+                let normal_idx = Self::parse_index(string, idx, self.normals.len())?;
+                Some(self.normals[normal_idx])
+            }
+            _ => None,
+        };
 
-        let skinning_vertex = skinning.map(|it| &it.vertices[point_idx]);
+        let skinning_vertex = match skinning {
+            Some(skinning) => Some(
+                skinning
+                    .vertices
+                    .get(point_idx)
+                    .ok_or_else(|| ParseError::at_token(string, "Vertex has no skinning data"))?,
+            ),
+            None => None,
+        };
 
-        RawVertexData {
+        Ok(RawVertexData {
             point,
-            texture_coord: texture,
-            normal: if split.len() < 3 {
-                None
-            } else {
-                let normal_idx: usize = split[2].parse().unwrap();
-                Some(self.normals[normal_idx - 1])
-            },
+            texture_coord,
+            normal,
             bones: skinning_vertex.map(|it| it.bones),
             weights: skinning_vertex.map(|it| it.weights),
+            color: self.colors[point_idx],
+        })
+    }
+
+    fn parse_index(vertex: &str, index: &str, len: usize) -> ParseResult<usize> {
+        let idx: isize = index
+            .parse()
+            .map_err(|_| ParseError::at_token(vertex, "Expected an integer index"))?;
+        let resolved = if idx < 0 { len as isize + idx } else { idx - 1 };
+        if idx == 0 || resolved < 0 || resolved >= len as isize {
+            return Err(ParseError::at_token(vertex, "Index is out of range"));
         }
+        Ok(resolved as usize)
+    }
+}
+
+/// Writes triangle lists in the subset of OBJ that `ObjParser` reads.
+///
+/// Parsing the text gives the same triangle list back, bit for bit, as long as every `v`
+/// texture coordinate is zero or between `2^-29` and `2^52` in magnitude. Flipping it
+/// is exact in double precision there.
+pub struct ObjWriter {
+    points: Vec<(Vector3, Vector4, Vector4, Vector3)>,
+    texture_coords: Vec<Vector2>,
+    normals: Vec<Vector3>,
+    faces: Vec<[[usize; 3]; 3]>,
+    point_ids: HashMap<[u32; 14], usize>,
+    texture_coord_ids: HashMap<[u32; 2], usize>,
+    normal_ids: HashMap<[u32; 3], usize>,
+}
+
+impl ObjWriter {
+    pub fn write(vertices: &[VertexData]) -> String {
+        Self::new(vertices).write_obj()
+    }
+
+    /// Skinned vertices also produce the `.skin` file `ObjParser::parse_with_skin` expects.
+    pub fn write_with_skin(vertices: &[VertexData]) -> (String, Skinning) {
+        let writer = Self::new(vertices);
+        let skinning = Skinning {
+            vertices: writer
+                .points
+                .iter()
+                .map(|(_, bones, weights, _)| SkinningVertex {
+                    bones: *bones,
+                    weights: *weights,
+                    discarded: 0.0,
+                })
+                .collect(),
+        };
+        (writer.write_obj(), skinning)
+    }
+
+    fn new(vertices: &[VertexData]) -> Self {
+        let mut writer = Self {
+            points: vec![],
+            texture_coords: vec![],
+            normals: vec![],
+            faces: vec![],
+            point_ids: HashMap::new(),
+            texture_coord_ids: HashMap::new(),
+            normal_ids: HashMap::new(),
+        };
+        for triangle in vertices.chunks_exact(3) {
+            let mut face = [[0; 3]; 3];
+            for (corner, vertex) in face.iter_mut().zip(triangle) {
+                *corner = [
+                    writer.point_id(vertex),
+                    writer.texture_coord_id(vertex.texture_coord),
+                    writer.normal_id(vertex.normal),
+                ];
+            }
+            writer.faces.push(face);
+        }
+        writer
+    }
+
+    fn point_id(&mut self, vertex: &VertexData) -> usize {
+        let mut key = [0; 14];
+        key.copy_from_slice(&vertex.key()[..14]);
+        let points = &mut self.points;
+        *self.point_ids.entry(key).or_insert_with(|| {
+            points.push((vertex.point, vertex.bones, vertex.weights, vertex.color));
+            points.len()
+        })
+    }
+
+    fn texture_coord_id(&mut self, coord: Vector2) -> usize {
+        let key = [coord.x().to_bits(), coord.y().to_bits()];
+        let texture_coords = &mut self.texture_coords;
+        *self.texture_coord_ids.entry(key).or_insert_with(|| {
+            texture_coords.push(coord);
+            texture_coords.len()
+        })
+    }
+
+    fn normal_id(&mut self, normal: Vector3) -> usize {
+        let key = [
+            normal.x().to_bits(),
+            normal.y().to_bits(),
+            normal.z().to_bits(),
+        ];
+        let normals = &mut self.normals;
+        *self.normal_ids.entry(key).or_insert_with(|| {
+            normals.push(normal);
+            normals.len()
+        })
+    }
+
+    fn write_obj(&self) -> String {
+        let mut file = String::new();
+        for (point, _, _, color) in self.points.iter() {
+            if color.vector == VertexData::default_color().vector {
+                writeln!(file, "v {}", write_point_3(*point)).unwrap();
+            } else {
+                writeln!(
+                    file,
+                    "v {} {}",
+                    write_point_3(*point),
+                    write_point_3(*color)
+                )
+                .unwrap();
+            }
+        }
+        for coord in self.texture_coords.iter() {
+            let flipped = 1.0 - coord.y() as f64;
+            writeln!(file, "vt {} {}", coord.x(), flipped).unwrap();
+        }
+        for normal in self.normals.iter() {
+            writeln!(file, "vn {}", write_point_3(*normal)).unwrap();
+        }
+        for face in self.faces.iter() {
+            write!(file, "f").unwrap();
+            for [point, coord, normal] in face.iter() {
+                write!(file, " {}/{}/{}", point, coord, normal).unwrap();
+            }
+            writeln!(file).unwrap();
+        }
+        file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_same_vertices(a: &[VertexData], b: &[VertexData]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b.iter()) {
+            // Bit patterns, so `-0.0` and `0.0` are told apart
+            assert_eq!(a.key(), b.key(), "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let vertices = ObjParser::parse(include_str!("../../../resources/person.obj"));
+        let parsed = ObjParser::parse(&ObjWriter::write(&vertices));
+        assert_same_vertices(&vertices, &parsed);
+    }
+
+    #[test]
+    fn write_round_trip_with_skin() {
+        let skin = Skinning::parse(include_str!("../../../resources/walk.skin"));
+        let vertices =
+            ObjParser::parse_with_skin(include_str!("../../../resources/walk.obj"), &skin);
+
+        let (file, written_skin) = ObjWriter::write_with_skin(&vertices);
+        let parsed_skin = Skinning::parse(&written_skin.write());
+        let parsed = ObjParser::parse_with_skin(&file, &parsed_skin);
+        assert_same_vertices(&vertices, &parsed);
+    }
+
+    #[test]
+    fn write_keeps_texture_coords() {
+        let coords = [
+            0.0,
+            1.0,
+            0.5,
+            0.1,
+            0.3,
+            0.7,
+            1e-3,
+            0.999_999_94,
+            -0.25,
+            2.5,
+            123.456,
+            2f32.powi(-29),
+        ];
+        let vertices: Vec<VertexData> = coords
+            .iter()
+            .enumerate()
+            .map(|(idx, v)| VertexData {
+                point: Vector3::from_xyz(idx as f32, (idx % 3) as f32, (idx % 2) as f32),
+                normal: Vector3::from_xyz(0.0, 0.0, 1.0),
+                texture_coord: Vector2::from_xy(0.25, *v),
+                bones: Vector4::default(),
+                weights: Vector4::default(),
+                color: VertexData::default_color(),
+            })
+            .collect();
+
+        let parsed = ObjParser::parse(&ObjWriter::write(&vertices));
+        assert_same_vertices(&vertices, &parsed);
     }
 }
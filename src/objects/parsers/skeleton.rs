@@ -1,16 +1,19 @@
-use std::{cell::RefCell, collections::HashMap};
+use std::{cell::RefCell, collections::HashMap, fmt::Write};
 
 use crate::geometry::{transform::RawTransform, Matrix, Transform};
 
-use super::{obj_lines, parse_transform};
+use super::{
+    obj_lines, try_parse_quaternion_transform, try_parse_transform, write_transform, ObjLine,
+    ParseError, ParseResult,
+};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Skeleton {
     pub bones: Vec<Bone>,
     pub names: HashMap<String, usize>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Bone {
     pub parent: isize,
     pub name: String,
@@ -36,28 +39,77 @@ impl BoneTransform {
 
 impl Skeleton {
     pub fn from_file(file: &str) -> Self {
+        Self::try_from_file(file).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Bones take their initial transform from `bb` with Euler angles or `bq` with a quaternion.
+    pub fn try_from_file(file: &str) -> ParseResult<Self> {
         let mut skl = Self {
             bones: vec![],
             names: HashMap::new(),
         };
-        for split in obj_lines(file) {
-            match split[0] {
+        // Parents may be declared after their children, they are resolved in the end
+        let mut parents = vec![];
+        for line in obj_lines(file) {
+            match line[0] {
                 "b" => {
+                    let name = line.get(1).ok_or_else(|| line.error("Bone has no name"))?;
                     skl.bones.push(Bone {
-                        name: split[1].to_string(),
+                        name: name.to_string(),
                         parent: -1,
                         initial_transform: RawTransform::new(),
                     });
-                    skl.names.insert(split[1].to_string(), skl.bones.len() - 1);
+                    skl.names.insert(name.to_string(), skl.bones.len() - 1);
+                }
+                "bp" => {
+                    let name = line
+                        .get(1)
+                        .ok_or_else(|| line.error("Bone parent has no name"))?;
+                    skl.last_bone(&line)?;
+                    parents.push((
+                        skl.bones.len() - 1,
+                        name.to_string(),
+                        line.locate(ParseError::at_token(name, "Unknown parent bone")),
+                    ));
                 }
-                "bp" => skl.bones.last_mut().unwrap().parent = skl.names[split[1]] as isize,
                 "bb" => {
-                    skl.bones.last_mut().unwrap().initial_transform = parse_transform(&split[1..]);
+                    let transform =
+                        try_parse_transform(&line[1..]).map_err(|err| line.locate(err))?;
+                    skl.last_bone(&line)?.initial_transform = transform;
+                }
+                "bq" => {
+                    let transform = try_parse_quaternion_transform(&line[1..])
+                        .map_err(|err| line.locate(err))?;
+                    skl.last_bone(&line)?.initial_transform = transform;
                 }
                 _ => continue,
             }
         }
-        skl
+        for (bone, name, error) in parents {
+            let parent = *skl.names.get(&name).ok_or(error)?;
+            skl.bones[bone].parent = parent as isize;
+        }
+        Ok(skl)
+    }
+
+    /// Text in the `.skl` format, bone names must not contain spaces.
+    pub fn write(&self) -> String {
+        let mut file = String::new();
+        for bone in self.bones.iter() {
+            writeln!(file, "b {}", bone.name).unwrap();
+            if bone.parent >= 0 {
+                writeln!(file, "bp {}", self.bones[bone.parent as usize].name).unwrap();
+            }
+            writeln!(file, "bq {}", write_transform(&bone.initial_transform)).unwrap();
+            writeln!(file).unwrap();
+        }
+        file
+    }
+
+    fn last_bone(&mut self, line: &ObjLine) -> ParseResult<&mut Bone> {
+        self.bones
+            .last_mut()
+            .ok_or_else(|| line.error("Bone property appears before any bone"))
     }
 
     pub fn make_nested_transforms(&self, parent: Transform) -> Vec<BoneTransform> {
@@ -78,3 +130,39 @@ impl Skeleton {
         transforms
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::{Quaternion, Vector3};
+
+    use super::*;
+
+    #[test]
+    fn write_round_trip() {
+        let skl = Skeleton::from_file(include_str!("../../../resources/person.skl"));
+        let written = skl.write();
+        let parsed = Skeleton::from_file(&written);
+
+        assert_eq!(skl, parsed);
+        assert_eq!(written, parsed.write());
+    }
+
+    #[test]
+    fn write_keeps_scale() {
+        let mut transform = RawTransform::new();
+        transform.position = Vector3::from_xyz(1.0, -2.5, 0.125);
+        transform.rotation = Quaternion::from_axis_angle(Vector3::from_xyz(0.0, 1.0, 0.0), 0.5);
+        transform.scale = Vector3::from_xyz(2.0, 0.5, 3.0);
+        let skl = Skeleton {
+            bones: vec![Bone {
+                name: "root".to_string(),
+                parent: -1,
+                initial_transform: transform,
+            }],
+            names: HashMap::from([("root".to_string(), 0)]),
+        };
+
+        let parsed = Skeleton::from_file(&skl.write());
+        assert_eq!(transform, parsed.bones[0].initial_transform);
+    }
+}
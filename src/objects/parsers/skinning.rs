@@ -1,48 +1,223 @@
-use crate::{geometry::vector::Vector4, objects::parsers::parse_point_2};
+use std::fmt::Write;
 
-use super::obj_lines;
+use crate::geometry::vector::Vector4;
 
-#[derive(Debug)]
+use super::{obj_lines, try_parse_point_2, ParseError, ParseResult};
+
+/// Bone slots of a vertex, the shaders blend at most this many bones.
+pub const MAX_INFLUENCES: usize = 4;
+
+#[derive(Debug, PartialEq)]
 pub struct Skinning {
     pub vertices: Vec<SkinningVertex>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct SkinningVertex {
     pub bones: Vector4,
     pub weights: Vector4,
+    /// Weight of the influences left out when loading, before normalization
+    pub discarded: f32,
+}
+
+/// How influences from the file are reduced to `MAX_INFLUENCES`, the strongest ones are kept.
+#[derive(Clone, Debug)]
+pub struct SkinningOptions {
+    /// Influences with a smaller weight are dropped, the strongest one always stays
+    pub threshold: f32,
+    /// Scales the kept weights to sum up to one
+    pub normalize: bool,
+}
+
+impl Default for SkinningOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 0.0,
+            normalize: true,
+        }
+    }
 }
 
 impl Skinning {
     pub fn parse(file: &str) -> Self {
+        Self::try_parse(file).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse(file: &str) -> ParseResult<Self> {
+        Self::try_parse_with_options(file, &SkinningOptions::default())
+    }
+
+    pub fn parse_with_options(file: &str, options: &SkinningOptions) -> Self {
+        Self::try_parse_with_options(file, options).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse_with_options(file: &str, options: &SkinningOptions) -> ParseResult<Self> {
         let mut skin = Self { vertices: vec![] };
 
-        for split in obj_lines(file) {
-            match split[0] {
-                "vl" => skin
-                    .vertices
-                    .push(Self::parse_skinning_vertices(&split[2..])),
+        for line in obj_lines(file) {
+            match line[0] {
+                "vl" => {
+                    if line.len() < 2 {
+                        return Err(line.error("Skinning vertex has no index"));
+                    }
+                    let vertex = Self::parse_skinning_vertices(&line[2..], options)
+                        .map_err(|err| line.locate(err))?;
+                    skin.vertices.push(vertex);
+                }
                 _ => continue,
             }
         }
 
-        skin
+        Ok(skin)
+    }
+
+    /// Largest weight any vertex lost to `SkinningOptions`.
+    pub fn max_discarded(&self) -> f32 {
+        self.vertices
+            .iter()
+            .map(|it| it.discarded)
+            .fold(0.0, f32::max)
+    }
+
+    /// Text in the `.skin` format, unused trailing bone slots are left out.
+    /// The `discarded` weights are gone from the text, they parse back as zero.
+    pub fn write(&self) -> String {
+        let mut file = String::new();
+        for (idx, vertex) in self.vertices.iter().enumerate() {
+            write!(file, "vl {}", idx + 1).unwrap();
+            let used = (0..4)
+                .rev()
+                .find(|i| vertex.bones.get(*i) != 0.0 || vertex.weights.get(*i) != 0.0)
+                .map_or(0, |i| i + 1);
+            for i in 0..used {
+                write!(file, " {} {}", vertex.bones.get(i), vertex.weights.get(i)).unwrap();
+            }
+            writeln!(file).unwrap();
+        }
+        file
     }
 
-    fn parse_skinning_vertices(split: &[&str]) -> SkinningVertex {
-        if split.len() % 2 != 0 || split.len() > 8 {
-            panic!("Invalid skinning vertex {:?}", split);
+    fn parse_skinning_vertices(
+        split: &[&str],
+        options: &SkinningOptions,
+    ) -> ParseResult<SkinningVertex> {
+        if split.len() % 2 != 0 {
+            return Err(ParseError::new(format!(
+                "Invalid skinning vertex {:?}",
+                split
+            )));
         }
 
+        let mut influences = split
+            .chunks(2)
+            .map(try_parse_point_2)
+            .collect::<ParseResult<Vec<_>>>()?;
+        // Stable, so equal weights keep the order of the file
+        influences.sort_by(|a, b| b.y.total_cmp(&a.y));
+        let kept = influences
+            .iter()
+            .take(MAX_INFLUENCES)
+            .enumerate()
+            .take_while(|(i, it)| *i == 0 || it.y >= options.threshold)
+            .count();
+        let kept_weight = influences[..kept].iter().fold(0.0, |sum, it| sum + it.y);
+        let discarded = influences[kept..].iter().fold(0.0, |sum, it| sum + it.y);
+        // Weights normalized before only differ from one by rounding, they are left as they are
+        let scale = if options.normalize && kept_weight > 0.0 && (kept_weight - 1.0).abs() > 1e-5 {
+            1.0 / kept_weight
+        } else {
+            1.0
+        };
+
         let mut bones = Vector4::from_xyzw(0.0, 0.0, 0.0, 0.0);
         let mut weights = Vector4::from_xyzw(0.0, 0.0, 0.0, 0.0);
-
-        for i in 0..(split.len() / 2) {
-            let pair = parse_point_2(&split[(2 * i)..(2 * i + 2)]);
+        for (i, pair) in influences[..kept].iter().enumerate() {
             bones.set(i, pair.x);
-            weights.set(i, pair.y);
+            weights.set(i, pair.y * scale);
+        }
+
+        Ok(SkinningVertex {
+            bones,
+            weights,
+            discarded,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_round_trip() {
+        let skin = Skinning::parse(include_str!("../../../resources/person.skin"));
+        let parsed = Skinning::parse(&skin.write());
+
+        // Influences dropped on load are not written, so nothing is discarded the second time
+        assert_eq!(skin.vertices.len(), parsed.vertices.len());
+        for (vertex, parsed) in skin.vertices.iter().zip(parsed.vertices.iter()) {
+            assert_eq!(vertex.bones, parsed.bones);
+            assert_eq!(vertex.weights, parsed.weights);
+            assert_eq!(parsed.discarded, 0.0);
         }
+        assert_eq!(Skinning::parse(&parsed.write()), parsed);
+    }
+
+    /// Six influences summing up to one, bones 3 and 6 have the same weight.
+    const SIX: &str = "vl 1 1 0.05 2 0.3 3 0.1 4 0.25 5 0.2 6 0.1\n";
+
+    fn assert_vertex(vertex: &SkinningVertex, bones: [f32; 4], weights: [f32; 4], discarded: f32) {
+        let [x, y, z, w] = bones;
+        assert_eq!(vertex.bones, Vector4::from_xyzw(x, y, z, w));
+        for (i, weight) in weights.iter().enumerate() {
+            assert!(
+                (vertex.weights.get(i) - weight).abs() < 1e-6,
+                "{:?}",
+                vertex
+            );
+        }
+        assert!((vertex.discarded - discarded).abs() < 1e-6, "{:?}", vertex);
+    }
+
+    #[test]
+    fn keeps_the_strongest_influences() {
+        let skin = Skinning::parse(SIX);
+        // Bone 3 comes before bone 6 with the same weight
+        let kept = [0.3, 0.25, 0.2, 0.1].map(|it| it / 0.85);
+        assert_vertex(&skin.vertices[0], [2.0, 4.0, 5.0, 3.0], kept, 0.15);
+        let sum = (0..4).map(|i| skin.vertices[0].weights.get(i)).sum::<f32>();
+        assert!((sum - 1.0).abs() < 1e-6);
+
+        let options = SkinningOptions {
+            threshold: 0.0,
+            normalize: false,
+        };
+        let skin = Skinning::parse_with_options(SIX, &options);
+        assert_vertex(
+            &skin.vertices[0],
+            [2.0, 4.0, 5.0, 3.0],
+            [0.3, 0.25, 0.2, 0.1],
+            0.15,
+        );
+    }
+
+    #[test]
+    fn threshold_drops_weak_influences() {
+        let options = |threshold| SkinningOptions {
+            threshold,
+            normalize: true,
+        };
+        let skin = Skinning::parse_with_options(SIX, &options(0.15));
+        let kept = [0.3 / 0.75, 0.25 / 0.75, 0.2 / 0.75, 0.0];
+        assert_vertex(&skin.vertices[0], [2.0, 4.0, 5.0, 0.0], kept, 0.25);
 
-        SkinningVertex { bones, weights }
+        // The strongest influence stays even below the threshold
+        let skin = Skinning::parse_with_options(SIX, &options(0.5));
+        assert_vertex(
+            &skin.vertices[0],
+            [2.0, 0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            0.7,
+        );
     }
 }
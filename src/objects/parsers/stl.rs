@@ -0,0 +1,168 @@
+use crate::geometry::Vector3;
+
+use super::{
+    normals::SmoothingOptions,
+    obj_lines,
+    shape::{ObjMesh, RawVertexData, VertexData},
+    try_parse_point_3, ParseError, ParseResult,
+};
+
+const HEADER_SIZE: usize = 84;
+const TRIANGLE_SIZE: usize = 50;
+
+/// Reads ASCII and binary STL files into the same triangle lists `ObjParser` produces.
+///
+/// Faces keep the normal stored in the file, the ones with a zero normal are flat shaded.
+/// STL has no texture coordinates, so they are projected on the plane of every face.
+pub struct StlParser;
+
+impl StlParser {
+    pub fn parse(data: &[u8]) -> Vec<VertexData> {
+        Self::try_parse(data).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse(data: &[u8]) -> ParseResult<Vec<VertexData>> {
+        Ok(Self::try_parse_mesh(data)?.vertices)
+    }
+
+    pub fn parse_mesh(data: &[u8]) -> ObjMesh {
+        Self::try_parse_mesh(data).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// The mesh is named after the ASCII `solid`, binary files make a `default` mesh.
+    pub fn try_parse_mesh(data: &[u8]) -> ParseResult<ObjMesh> {
+        Self::try_parse_mesh_with_options(data, &SmoothingOptions::default())
+    }
+
+    pub fn parse_mesh_with_options(data: &[u8], options: &SmoothingOptions) -> ObjMesh {
+        Self::try_parse_mesh_with_options(data, options).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_parse_mesh_with_options(
+        data: &[u8],
+        options: &SmoothingOptions,
+    ) -> ParseResult<ObjMesh> {
+        let mut mesh = Self::parse_raw(data)?;
+        // Every triangle corner is a position of its own, there is nothing to share
+        mesh.points = mesh.vertices.len();
+        mesh.smooth_normals(options);
+        Ok(mesh)
+    }
+
+    fn parse_raw(data: &[u8]) -> ParseResult<ObjMesh> {
+        if Self::is_binary(data) {
+            return Self::parse_binary(data);
+        }
+        if !data.trim_ascii_start().starts_with(b"solid") {
+            return Err(ParseError::new(if data.len() >= HEADER_SIZE {
+                "Binary STL file size does not match its triangle count"
+            } else {
+                "Not an STL file"
+            }));
+        }
+        let file = std::str::from_utf8(data)
+            .map_err(|_| ParseError::new("ASCII STL file is not valid UTF-8"))?;
+        Self::parse_ascii(file)
+    }
+
+    /// Binary files may start with `solid` as well, so their size decides.
+    fn is_binary(data: &[u8]) -> bool {
+        if data.len() < HEADER_SIZE {
+            return false;
+        }
+        let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+        count
+            .checked_mul(TRIANGLE_SIZE)
+            .and_then(|it| it.checked_add(HEADER_SIZE))
+            == Some(data.len())
+    }
+
+    fn parse_binary(data: &[u8]) -> ParseResult<ObjMesh> {
+        let mut mesh = ObjMesh::new("default");
+        for triangle in data[HEADER_SIZE..].chunks_exact(TRIANGLE_SIZE) {
+            let vector = |idx: usize| {
+                let float = |offset: usize| {
+                    let offset = idx * 12 + offset * 4;
+                    f32::from_le_bytes([
+                        triangle[offset],
+                        triangle[offset + 1],
+                        triangle[offset + 2],
+                        triangle[offset + 3],
+                    ])
+                };
+                Vector3::from_xyz(float(0), float(1), float(2))
+            };
+            let points = (1..4).map(|idx| RawVertexData::new(vector(idx))).collect();
+            Self::push_facet(&mut mesh, vector(0), points);
+        }
+        Ok(mesh)
+    }
+
+    fn parse_ascii(file: &str) -> ParseResult<ObjMesh> {
+        let mut mesh: Option<ObjMesh> = None;
+        let mut facet: Option<(Vector3, Vec<RawVertexData>)> = None;
+        let mut in_loop = false;
+
+        for line in obj_lines(file) {
+            match line[0] {
+                "solid" => {
+                    if mesh.is_none() {
+                        let name = line[1..].join(" ");
+                        let name = if name.is_empty() { "default" } else { &name };
+                        mesh = Some(ObjMesh::new(name));
+                    }
+                }
+                "facet" => {
+                    if facet.is_some() {
+                        return Err(line.error("Facet starts before the previous one ends"));
+                    }
+                    if line.get(1) != Some(&"normal") {
+                        return Err(line.error("Expected a facet normal"));
+                    }
+                    let normal = try_parse_point_3(&line[2..]).map_err(|err| line.locate(err))?;
+                    facet = Some((normal, vec![]));
+                }
+                "outer" => {
+                    if facet.is_none() {
+                        return Err(line.error("Loop outside of a facet"));
+                    }
+                    in_loop = true;
+                }
+                "vertex" => {
+                    let point = try_parse_point_3(&line[1..]).map_err(|err| line.locate(err))?;
+                    match facet.as_mut() {
+                        Some((_, points)) if in_loop => points.push(RawVertexData::new(point)),
+                        _ => return Err(line.error("Vertex outside of a facet loop")),
+                    }
+                }
+                "endloop" => in_loop = false,
+                "endfacet" => {
+                    let (normal, points) = facet
+                        .take()
+                        .ok_or_else(|| line.error("Unexpected endfacet"))?;
+                    if points.len() < 3 {
+                        return Err(line.error("Facet has fewer than 3 vertices"));
+                    }
+                    let mesh = mesh.get_or_insert_with(|| ObjMesh::new("default"));
+                    Self::push_facet(mesh, normal, points);
+                }
+                "endsolid" => continue,
+                _ => return Err(line.locate(ParseError::at_token(line[0], "Unknown keyword"))),
+            }
+        }
+        if facet.is_some() {
+            return Err(ParseError::new("STL file ends inside of a facet"));
+        }
+
+        Ok(mesh.unwrap_or_else(|| ObjMesh::new("default")))
+    }
+
+    fn push_facet(mesh: &mut ObjMesh, normal: Vector3, mut points: Vec<RawVertexData>) {
+        if normal.length() > 0.0 {
+            for point in points.iter_mut() {
+                point.normal = Some(normal.normalized());
+            }
+        }
+        mesh.push_polygon(points, &None, 0);
+    }
+}
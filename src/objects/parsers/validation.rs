@@ -0,0 +1,382 @@
+use std::fmt::{self, Display};
+
+use crate::{geometry::vector::Vector4, shaders::MAX_BONES};
+
+use super::{
+    animation::Animation,
+    shape::{IndexedMesh, ObjMesh, VertexData},
+    skeleton::Skeleton,
+    skinning::Skinning,
+};
+
+/// A mismatch between assets that parse fine on their own.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AssetProblem {
+    /// The skeleton does not fit the shader bone array next to the identity in slot 0.
+    TooManyBones {
+        bones: usize,
+        max: usize,
+    },
+    /// Skin entries refer to bones the skeleton does not have, `vertex` is the first `vl` index.
+    UnknownSkinBones {
+        bones: Vec<f32>,
+        vertex: usize,
+        count: usize,
+    },
+    /// Mesh vertices refer to bones the skeleton does not have, `vertex` is the first one.
+    UnknownMeshBones {
+        mesh: String,
+        bones: Vec<f32>,
+        vertex: usize,
+        count: usize,
+    },
+    /// The skin has a different number of entries than the OBJ file has positions.
+    SkinVertexCount {
+        mesh: String,
+        skin: usize,
+        points: usize,
+    },
+    /// Frames do not have one transform per bone, `frame` is the first one, numbered from 1.
+    FrameBoneCount {
+        animation: String,
+        frame: usize,
+        transforms: usize,
+        bones: usize,
+        count: usize,
+    },
+    NoFrames {
+        animation: String,
+    },
+}
+
+impl Display for AssetProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyBones { bones, max } => write!(
+                f,
+                "Skeleton has {} bones, shaders support at most {}",
+                bones, max
+            ),
+            Self::UnknownSkinBones {
+                bones,
+                vertex,
+                count,
+            } => write!(
+                f,
+                "{} skin vertices starting at {} use unknown bones {}",
+                count,
+                vertex,
+                format_bones(bones)
+            ),
+            Self::UnknownMeshBones {
+                mesh,
+                bones,
+                vertex,
+                count,
+            } => write!(
+                f,
+                "{} vertices of mesh {} starting at {} use unknown bones {}",
+                count,
+                mesh,
+                vertex,
+                format_bones(bones)
+            ),
+            Self::SkinVertexCount { mesh, skin, points } => write!(
+                f,
+                "Skin has {} vertices, mesh {} has {}",
+                skin, mesh, points
+            ),
+            Self::FrameBoneCount {
+                animation,
+                frame,
+                transforms,
+                bones,
+                count,
+            } => write!(
+                f,
+                "Animation {} frame {} has {} transforms for {} bones ({} frames in total)",
+                animation, frame, transforms, bones, count
+            ),
+            Self::NoFrames { animation } => write!(f, "Animation {} has no frames", animation),
+        }
+    }
+}
+
+fn format_bones(bones: &[f32]) -> String {
+    let bones: Vec<_> = bones.iter().map(|it| it.to_string()).collect();
+    bones.join(", ")
+}
+
+/// Checks that a skeleton, its skin, meshes and animations fit together.
+///
+/// Bone indices are checked against the skeleton when there is one, frames are checked
+/// against it or, without it, against the skeleton of the animation itself.
+pub struct AssetValidator<'a> {
+    skeleton: Option<&'a Skeleton>,
+    skinning: Option<&'a Skinning>,
+    meshes: Vec<(&'a str, &'a [VertexData], Option<usize>)>,
+    animations: Vec<(&'a str, &'a Animation)>,
+}
+
+impl<'a> AssetValidator<'a> {
+    pub fn new() -> Self {
+        Self {
+            skeleton: None,
+            skinning: None,
+            meshes: vec![],
+            animations: vec![],
+        }
+    }
+
+    pub fn with_skeleton(mut self, skeleton: &'a Skeleton) -> Self {
+        self.skeleton = Some(skeleton);
+        self
+    }
+
+    pub fn with_skinning(mut self, skinning: &'a Skinning) -> Self {
+        self.skinning = Some(skinning);
+        self
+    }
+
+    /// The skin is also checked to have one entry per position of the OBJ file.
+    pub fn with_mesh(mut self, mesh: &'a ObjMesh) -> Self {
+        self.meshes
+            .push((&mesh.name, &mesh.vertices, Some(mesh.points)));
+        self
+    }
+
+    pub fn with_indexed_mesh(mut self, mesh: &'a IndexedMesh) -> Self {
+        self.meshes.push((&mesh.name, &mesh.vertices, None));
+        self
+    }
+
+    pub fn with_animation(mut self, name: &'a str, animation: &'a Animation) -> Self {
+        self.animations.push((name, animation));
+        self
+    }
+
+    /// Every problem found, empty when the assets are consistent.
+    pub fn validate(&self) -> Vec<AssetProblem> {
+        let mut problems = vec![];
+
+        if let Some(skeleton) = self.skeleton {
+            if skeleton.bones.len() >= MAX_BONES {
+                problems.push(AssetProblem::TooManyBones {
+                    bones: skeleton.bones.len(),
+                    max: MAX_BONES - 1,
+                });
+            }
+            if let Some(skinning) = self.skinning {
+                let bones = skinning.vertices.iter().map(|it| it.bones);
+                if let Some((bones, vertex, count)) = unknown_bones(bones, skeleton) {
+                    problems.push(AssetProblem::UnknownSkinBones {
+                        bones,
+                        vertex: vertex + 1,
+                        count,
+                    });
+                }
+            }
+            for (mesh, vertices, _) in self.meshes.iter() {
+                let bones = vertices.iter().map(|it| it.bones);
+                if let Some((bones, vertex, count)) = unknown_bones(bones, skeleton) {
+                    problems.push(AssetProblem::UnknownMeshBones {
+                        mesh: mesh.to_string(),
+                        bones,
+                        vertex,
+                        count,
+                    });
+                }
+            }
+        }
+
+        if let Some(skinning) = self.skinning {
+            let obj_meshes = self
+                .meshes
+                .iter()
+                .filter_map(|(mesh, _, points)| points.map(|it| (mesh, it)));
+            for (mesh, points) in obj_meshes {
+                if points != skinning.vertices.len() {
+                    problems.push(AssetProblem::SkinVertexCount {
+                        mesh: mesh.to_string(),
+                        skin: skinning.vertices.len(),
+                        points,
+                    });
+                }
+            }
+        }
+
+        for (name, animation) in self.animations.iter() {
+            if animation.frames.is_empty() {
+                problems.push(AssetProblem::NoFrames {
+                    animation: name.to_string(),
+                });
+                continue;
+            }
+            let bones = self.skeleton.unwrap_or(&animation.skeleton).bones.len();
+            let mut wrong = animation
+                .frames
+                .iter()
+                .enumerate()
+                .filter(|(_, frame)| frame.transforms.len() != bones);
+            if let Some((frame, first)) = wrong.next() {
+                problems.push(AssetProblem::FrameBoneCount {
+                    animation: name.to_string(),
+                    frame: frame + 1,
+                    transforms: first.transforms.len(),
+                    bones,
+                    count: wrong.count() + 1,
+                });
+            }
+        }
+
+        problems
+    }
+}
+
+impl<'a> Default for AssetValidator<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sorted bone indices outside of the skeleton, the first vertex using one and the number of
+/// such vertices. Skin bones are numbered from 1, index 0 is the identity.
+fn unknown_bones(
+    vertices: impl Iterator<Item = Vector4>,
+    skeleton: &Skeleton,
+) -> Option<(Vec<f32>, usize, usize)> {
+    let mut bones: Vec<f32> = vec![];
+    let mut first = None;
+    let mut count = 0;
+    for (idx, vertex) in vertices.enumerate() {
+        let mut known = true;
+        for i in 0..4 {
+            let bone = vertex.get(i);
+            if bone >= 0.0 && bone.fract() == 0.0 && bone as usize <= skeleton.bones.len() {
+                continue;
+            }
+            known = false;
+            if !bones.contains(&bone) {
+                bones.push(bone);
+            }
+        }
+        if !known {
+            first.get_or_insert(idx);
+            count += 1;
+        }
+    }
+    bones.sort_by(f32::total_cmp);
+    first.map(|first| (bones, first, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::objects::parsers::shape::ObjParser;
+
+    use super::*;
+
+    /// Root with a child, both at the origin.
+    fn two_bones() -> Rc<Skeleton> {
+        Rc::new(Skeleton::from_file("b root\nb tip\nbp root\n"))
+    }
+
+    #[test]
+    fn shipped_person_has_no_problems() {
+        let skl = Rc::new(Skeleton::from_file(include_str!(
+            "../../../resources/person.skl"
+        )));
+        let skin = Skinning::parse(include_str!("../../../resources/person.skin"));
+        let mesh =
+            ObjParser::parse_mesh(include_str!("../../../resources/person.obj"), Some(&skin));
+        let anim = Animation::parse(include_str!("../../../resources/person.anim"), &skl);
+
+        let problems = AssetValidator::new()
+            .with_skeleton(&skl)
+            .with_skinning(&skin)
+            .with_mesh(&mesh)
+            .with_animation("person", &anim)
+            .validate();
+        assert_eq!(problems, vec![]);
+    }
+
+    #[test]
+    fn skeletons_leave_slot_0_to_the_identity() {
+        let skeleton = |bones: usize| {
+            let file: String = (0..bones).map(|it| format!("b bone{}\n", it)).collect();
+            Skeleton::from_file(&file)
+        };
+
+        let fits = skeleton(MAX_BONES - 1);
+        assert_eq!(
+            AssetValidator::new().with_skeleton(&fits).validate(),
+            vec![]
+        );
+        let too_many = skeleton(MAX_BONES);
+        assert_eq!(
+            AssetValidator::new().with_skeleton(&too_many).validate(),
+            vec![AssetProblem::TooManyBones {
+                bones: MAX_BONES,
+                max: MAX_BONES - 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn finds_skin_bones_outside_of_the_skeleton() {
+        let skl = two_bones();
+        // Bones are numbered from 1, so 2 is the last one
+        let skin = Skinning::parse("vl 1 2 1\nvl 2 3 1\nvl 3 1 0.5 3 0.5\n");
+        assert_eq!(
+            AssetValidator::new()
+                .with_skeleton(&skl)
+                .with_skinning(&skin)
+                .validate(),
+            vec![AssetProblem::UnknownSkinBones {
+                bones: vec![3.0],
+                vertex: 2,
+                count: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn finds_skins_for_other_meshes() {
+        let mesh = ObjParser::parse_mesh("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n", None);
+        let skin = Skinning::parse("vl 1 1 1\nvl 2 1 1\n");
+        assert_eq!(
+            AssetValidator::new()
+                .with_skinning(&skin)
+                .with_mesh(&mesh)
+                .validate(),
+            vec![AssetProblem::SkinVertexCount {
+                mesh: mesh.name.clone(),
+                skin: 2,
+                points: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn finds_truncated_frames() {
+        let skl = two_bones();
+        let anim = Animation::parse(
+            "fr 1\naf 0 0 0\naf 0 0 0\nfr 2\naf 0 0 0\nfr 3\naf 0 0 0\naf 0 0 0\n",
+            &skl,
+        );
+        assert_eq!(
+            AssetValidator::new()
+                .with_skeleton(&skl)
+                .with_animation("walk", &anim)
+                .validate(),
+            vec![AssetProblem::FrameBoneCount {
+                animation: "walk".to_string(),
+                frame: 2,
+                transforms: 1,
+                bones: 2,
+                count: 1,
+            }]
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::rc::Rc;
 use rand::Rng;
 use web_sys::WebGl2RenderingContext as Gl;
 
-use crate::geometry::{transform::RawTransform, Transform, Vector3};
+use crate::geometry::{transform::RawTransform, Quaternion, Transform, Vector3};
 
 use super::{shape::Shape, texture::Texture};
 
@@ -12,7 +12,6 @@ pub struct Particles {
     pub texture: Rc<Texture>,
     pub transform: Transform,
     pub particles: Vec<RawTransform>,
-    pub verts: i32,
     pub shape: Rc<Shape>,
     stride: i32,
 }
@@ -31,7 +30,6 @@ impl Particles {
             texture,
             transform: Transform::new(),
             particles: Self::random_particles(),
-            verts: shape.buffer_length(),
             shape,
             stride,
         }
@@ -41,10 +39,6 @@ impl Particles {
         self.stride
     }
 
-    pub fn vertices(&self) -> i32 {
-        self.verts
-    }
-
     fn random_particles() -> Vec<RawTransform> {
         use std::f32::consts::PI;
         let mut rand = rand::thread_rng();
@@ -57,11 +51,11 @@ impl Particles {
                 rand.gen_range(-1.0, 1.0),
                 rand.gen_range(-1.0, 1.0),
             ) * 3.0;
-            t.rotation = Vector3::from_xyz(
+            t.rotation = Quaternion::from_euler(Vector3::from_xyz(
                 rand.gen_range(-PI, PI),
                 rand.gen_range(-PI, PI),
                 rand.gen_range(-PI, PI),
-            );
+            ));
             vec.push(t);
         }
         vec
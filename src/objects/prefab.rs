@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::{collections::HashMap, rc::Rc};
 
 use crate::{
     geometry::{Transform, Vector3},
@@ -6,8 +6,8 @@ use crate::{
 };
 
 use super::{
-    object::Object, parsers::animation::Animation, rigid_body::RigidBody, shape::Shape,
-    texture::Texture,
+    material::ObjectMaterial, object::Object, parsers::animation::Animation, rigid_body::RigidBody,
+    shape::Shape, texture::Texture,
 };
 
 pub trait Prefab {
@@ -17,6 +17,7 @@ pub trait Prefab {
 pub struct ObjectPrefab {
     shape: Rc<Shape>,
     texture: Rc<Texture>,
+    materials: HashMap<String, Rc<ObjectMaterial>>,
     rigid_body: Option<(Vector3, Vector3, bool)>,
     animation: Option<Rc<Animation>>,
 }
@@ -26,6 +27,7 @@ impl ObjectPrefab {
         Self {
             shape: shape.clone(),
             texture: texture.clone(),
+            materials: HashMap::new(),
             rigid_body: None,
             animation: None,
         }
@@ -36,6 +38,11 @@ impl ObjectPrefab {
         self
     }
 
+    pub fn with_materials(mut self, materials: &HashMap<String, Rc<ObjectMaterial>>) -> Self {
+        self.materials = materials.clone();
+        self
+    }
+
     pub fn with_animation(mut self, animation: &Rc<Animation>) -> Self {
         self.animation = Some(animation.clone());
         self
@@ -44,7 +51,8 @@ impl ObjectPrefab {
 
 impl Prefab for ObjectPrefab {
     fn construct(&self, transform: Transform, world: &mut World) {
-        let mut object = Object::new(self.shape.clone(), self.texture.clone(), transform.clone());
+        let mut object = Object::new(self.shape.clone(), self.texture.clone(), transform.clone())
+            .with_materials(self.materials.clone());
         if let Some((size, offset, movable)) = self.rigid_body.as_ref() {
             let mut body_clone = RigidBody::new(*size, *offset, transform);
             if *movable {
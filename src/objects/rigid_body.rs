@@ -1,10 +1,82 @@
-use crate::geometry::{aabb::Aabb, raycast::Ray, Matrix, Transform, Vector3};
+use crate::geometry::{
+    aabb::Aabb, capsule::Capsule, obb::Obb, raycast::Ray, sphere::Sphere, Matrix, Transform,
+    Vector3,
+};
+
+/// Volume a body collides with, all of them follow the `center` transform of the body.
+#[derive(Clone)]
+pub enum BodyShape {
+    Aabb(Aabb),
+    /// Turns along with the body, it does not step onto slopes
+    Obb(Obb),
+    Sphere(Sphere),
+    /// Steps onto boxes like `Aabb`, without snagging on their corners
+    Capsule(Capsule),
+}
+
+impl BodyShape {
+    pub fn center(&self) -> &Transform {
+        match self {
+            Self::Aabb(aabb) => &aabb.center,
+            Self::Obb(obb) => &obb.center,
+            Self::Sphere(sphere) => &sphere.center,
+            Self::Capsule(capsule) => &capsule.center,
+        }
+    }
+
+    /// Shortest translation that moves `self` out of `other`.
+    pub fn find_mtv(&self, other: &Self) -> Option<Vector3> {
+        use BodyShape::*;
+
+        match (self, other) {
+            (Aabb(a), Aabb(b)) => a.find_mtv(b, Some(SLOPE_HEIGHT)),
+            (Obb(a), Obb(b)) => a.find_mtv(b),
+            (Sphere(a), Sphere(b)) => a.find_mtv(b),
+            (Capsule(a), Capsule(b)) => a.find_mtv(b),
+            (Obb(a), Aabb(b)) => a.find_mtv_aabb(b),
+            (Sphere(a), Aabb(b)) => a.find_mtv_aabb(b),
+            (Sphere(a), Obb(b)) => a.find_mtv_obb(b),
+            (Capsule(a), Aabb(b)) => a.find_mtv_aabb(b, Some(SLOPE_HEIGHT)),
+            (Capsule(a), Obb(b)) => a.find_mtv_obb(b),
+            (Capsule(a), Sphere(b)) => a.find_mtv_sphere(b),
+            (Aabb(_), _) | (Obb(_), _) | (Sphere(_), _) => other.find_mtv(self).map(|it| it * -1.0),
+        }
+    }
+
+    /// Lowest and highest corners of the axis-aligned box around the shape.
+    pub fn bounds(&self) -> (Vector3, Vector3) {
+        match self {
+            Self::Aabb(aabb) => aabb.bounds(),
+            Self::Obb(obb) => obb.bounds(),
+            Self::Sphere(sphere) => sphere.bounds(),
+            Self::Capsule(capsule) => capsule.bounds(),
+        }
+    }
+
+    pub fn cast_ray(&self, ray: &Ray) -> Option<(f32, Vector3)> {
+        match self {
+            Self::Aabb(aabb) => aabb.cast_ray(ray),
+            Self::Obb(obb) => obb.cast_ray(ray),
+            Self::Sphere(sphere) => sphere.cast_ray(ray),
+            Self::Capsule(capsule) => capsule.cast_ray(ray),
+        }
+    }
+
+    pub fn frame_matrix(&self) -> Matrix {
+        match self {
+            Self::Aabb(aabb) => aabb.frame_matrix(),
+            Self::Obb(obb) => obb.frame_matrix(),
+            Self::Sphere(sphere) => sphere.frame_matrix(),
+            Self::Capsule(capsule) => capsule.frame_matrix(),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct RigidBody {
     pub size: Vector3,
     pub offset: Vector3,
-    aabb: Aabb,
+    shape: BodyShape,
     pub transform: Transform,
     movable: bool,
 }
@@ -16,7 +88,7 @@ impl RigidBody {
         Self {
             size,
             offset,
-            aabb: Aabb::new(
+            shape: BodyShape::Aabb(Aabb::new(
                 {
                     let t = Transform::new();
                     t.translate_vec(offset);
@@ -24,7 +96,7 @@ impl RigidBody {
                     t
                 },
                 size,
-            ),
+            )),
             transform,
             movable: false,
         }
@@ -32,7 +104,32 @@ impl RigidBody {
 
     pub fn replace_transform(&mut self, transform: Transform) {
         self.transform = transform.clone();
-        self.aabb.center.set_parent(transform);
+        self.shape.center().set_parent(transform);
+    }
+
+    /// Collides as a box turning along with the transform instead of an axis-aligned one.
+    pub fn as_oriented(mut self) -> Self {
+        self.shape = BodyShape::Obb(Obb::new(self.shape.center().clone(), self.size));
+        self
+    }
+
+    /// Collides as the largest sphere fitting into the size.
+    pub fn as_sphere(mut self) -> Self {
+        let radius = self.size.x().min(self.size.y()).min(self.size.z()) * 0.5;
+        self.shape = BodyShape::Sphere(Sphere::new(self.shape.center().clone(), radius));
+        self
+    }
+
+    /// Collides as an upright capsule as tall as the size, e.g. for characters.
+    pub fn as_capsule(mut self) -> Self {
+        let radius = self.size.x().min(self.size.z()) * 0.5;
+        let capsule = Capsule::new(self.shape.center().clone(), radius, self.size.y());
+        self.shape = BodyShape::Capsule(capsule);
+        self
+    }
+
+    pub fn shape(&self) -> &BodyShape {
+        &self.shape
     }
 
     pub fn as_movable(mut self) -> Self {
@@ -41,7 +138,7 @@ impl RigidBody {
     }
 
     pub fn collide(&self, other: &Self) {
-        let mtv = self.aabb.find_mtv(&other.aabb, Some(SLOPE_HEIGHT));
+        let mtv = self.shape.find_mtv(&other.shape);
         if let Some(mtv) = mtv {
             if self.movable && other.movable {
                 self.transform.translate_vec(mtv * 0.5);
@@ -54,11 +151,15 @@ impl RigidBody {
         }
     }
 
+    pub fn bounds(&self) -> (Vector3, Vector3) {
+        self.shape.bounds()
+    }
+
     pub fn cast_ray(&self, ray: &Ray) -> Option<(f32, Vector3)> {
-        self.aabb.cast_ray(ray)
+        self.shape.cast_ray(ray)
     }
 
     pub fn frame_matrix(&self) -> Matrix {
-        self.aabb.frame_matrix()
+        self.shape.frame_matrix()
     }
 }
@@ -1,56 +1,244 @@
+use std::{cell::OnceCell, collections::HashMap, ops::Index, rc::Rc};
+
 use web_sys::{WebGl2RenderingContext, WebGlBuffer};
 
 use crate::{
-    geometry::{vector::Vector4, Vector2, Vector3},
-    shaders::make_f32_buffer,
+    geometry::{
+        raycast::Ray,
+        triangle_bvh::{TriangleBvh, TriangleHit},
+        vector::Vector4,
+        Vector2, Vector3,
+    },
+    shaders::{make_f32_buffer, make_u16_index_buffer, make_u32_index_buffer},
 };
 
 use super::parsers::{
-    shape::{ObjParser, VertexData},
+    shape::{IndexedMesh, ObjMesh, ObjMeshes, ObjParser, SubMesh, VertexData},
     skinning::Skinning,
+    ParseResult,
 };
 
 pub struct Shape {
     vertices: Vec<VertexData>,
+    indices: Vec<u32>,
+    submeshes: Vec<SubMesh>,
     buffer: WebGlBuffer,
+    index_buffer: WebGlBuffer,
+    index_type: u32,
+    bounds: (Vector3, Vector3),
+    bvh: OnceCell<TriangleBvh>,
+}
+
+/// Ray hit on the triangles of a shape, in the space the ray was cast in.
+#[derive(Clone, Copy, Debug)]
+pub struct ShapeHit {
+    pub distance: f32,
+    pub point: Vector3,
+    /// Weights of the triangle corners
+    pub barycentric: Vector3,
+    /// Unit normal of the triangle, not the interpolated vertex normal
+    pub normal: Vector3,
+    pub texture_coord: Vector2,
+    /// Index of the triangle, its corners are `indices()[3 * triangle..3 * triangle + 3]`
+    pub triangle: usize,
 }
 
 impl Shape {
     pub fn parse(file: &str, gl: &WebGl2RenderingContext) -> Self {
-        Self::new(ObjParser::parse(file), gl)
+        Self::from_mesh(ObjParser::parse_mesh(file, None), gl)
     }
 
     pub fn parse_with_skin(file: &str, skin: &Skinning, gl: &WebGl2RenderingContext) -> Self {
-        Self::new(ObjParser::parse_with_skin(file, skin), gl)
+        Self::from_mesh(ObjParser::parse_mesh(file, Some(skin)), gl)
+    }
+
+    pub fn try_parse(file: &str, gl: &WebGl2RenderingContext) -> ParseResult<Self> {
+        Ok(Self::from_mesh(ObjParser::try_parse_mesh(file, None)?, gl))
     }
 
-    fn new(vertices: Vec<VertexData>, gl: &WebGl2RenderingContext) -> Self {
+    pub fn try_parse_with_skin(
+        file: &str,
+        skin: &Skinning,
+        gl: &WebGl2RenderingContext,
+    ) -> ParseResult<Self> {
+        Ok(Self::from_mesh(
+            ObjParser::try_parse_mesh(file, Some(skin))?,
+            gl,
+        ))
+    }
+
+    /// One shape per OBJ object or group, e.g. to place parts of a level separately.
+    pub fn parse_named(file: &str, gl: &WebGl2RenderingContext) -> Shapes {
+        Shapes::from_meshes(ObjParser::parse_named(file, None), gl)
+    }
+
+    pub fn try_parse_named(file: &str, gl: &WebGl2RenderingContext) -> ParseResult<Shapes> {
+        Ok(Shapes::from_meshes(
+            ObjParser::try_parse_named(file, None)?,
+            gl,
+        ))
+    }
+
+    pub fn new(vertices: Vec<VertexData>, gl: &WebGl2RenderingContext) -> Self {
+        let submeshes = vec![SubMesh {
+            material: None,
+            start: 0,
+            count: vertices.len(),
+        }];
+        Self::with_submeshes(vertices, submeshes, gl)
+    }
+
+    pub fn from_mesh(mesh: ObjMesh, gl: &WebGl2RenderingContext) -> Self {
+        Self::with_submeshes(mesh.vertices, mesh.submeshes, gl)
+    }
+
+    /// Submesh ranges of the triangle list become ranges of the index buffer.
+    fn with_submeshes(
+        triangles: Vec<VertexData>,
+        submeshes: Vec<SubMesh>,
+        gl: &WebGl2RenderingContext,
+    ) -> Self {
+        Self::from_indexed(IndexedMesh::new("", &triangles, submeshes), gl)
+    }
+
+    /// Uploads an already deduplicated mesh, e.g. one loaded from an `AssetBundle`.
+    pub fn from_indexed(mesh: IndexedMesh, gl: &WebGl2RenderingContext) -> Self {
+        let (index_buffer, index_type) = if mesh.vertices.len() <= u16::MAX as usize + 1 {
+            let indices: Vec<u16> = mesh.indices.iter().map(|it| *it as u16).collect();
+            (
+                make_u16_index_buffer(gl, &indices),
+                WebGl2RenderingContext::UNSIGNED_SHORT,
+            )
+        } else {
+            (
+                make_u32_index_buffer(gl, &mesh.indices),
+                WebGl2RenderingContext::UNSIGNED_INT,
+            )
+        };
+
         Self {
-            buffer: make_f32_buffer(gl, &Self::make_buffer(&vertices)),
-            vertices,
+            buffer: make_f32_buffer(gl, &Self::make_buffer(&mesh.vertices)),
+            bounds: Self::make_bounds(&mesh.vertices),
+            index_buffer,
+            index_type,
+            vertices: mesh.vertices,
+            indices: mesh.indices,
+            submeshes: mesh.submeshes,
+            bvh: OnceCell::new(),
+        }
+    }
+
+    /// Corners of the box around all vertices in model space, zero for an empty shape.
+    pub fn bounds(&self) -> (Vector3, Vector3) {
+        self.bounds
+    }
+
+    /// Built on first use, shapes nobody casts rays at do not pay for it.
+    pub fn bvh(&self) -> &TriangleBvh {
+        self.bvh.get_or_init(|| {
+            let positions: Vec<Vector3> = self.vertices.iter().map(|it| it.point).collect();
+            TriangleBvh::new(&positions, &self.indices)
+        })
+    }
+
+    /// Closest hit of a ray in model space, see `Object::cast_ray` for world space.
+    pub fn cast_ray(&self, ray: &Ray) -> Option<ShapeHit> {
+        self.bvh().cast_ray(ray).map(|hit| self.shape_hit(hit))
+    }
+
+    /// Adds the interpolated texture coordinates to a hit on the triangles of the shape.
+    pub fn shape_hit(&self, hit: TriangleHit) -> ShapeHit {
+        ShapeHit {
+            distance: hit.distance,
+            point: hit.point,
+            barycentric: hit.barycentric,
+            normal: hit.normal,
+            texture_coord: Self::texture_coord(&self.vertices, &self.indices, &hit),
+            triangle: hit.triangle,
         }
     }
 
+    /// Texture coordinates of the corners blended by the barycentric weights of the hit.
+    fn texture_coord(vertices: &[VertexData], indices: &[u32], hit: &TriangleHit) -> Vector2 {
+        let corners = &indices[hit.triangle * 3..hit.triangle * 3 + 3];
+        let (mut u, mut v) = (0.0, 0.0);
+        for (i, idx) in corners.iter().enumerate() {
+            let texture_coord = vertices[*idx as usize].texture_coord;
+            u += texture_coord.x() * hit.barycentric.get(i);
+            v += texture_coord.y() * hit.barycentric.get(i);
+        }
+        Vector2::from_xy(u, v)
+    }
+
+    pub fn submeshes(&self) -> &[SubMesh] {
+        &self.submeshes
+    }
+
+    pub fn vertices(&self) -> &[VertexData] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
     pub fn get_buffer(&self) -> WebGlBuffer {
         self.buffer.clone()
     }
 
+    pub fn get_index_buffer(&self) -> WebGlBuffer {
+        self.index_buffer.clone()
+    }
+
+    /// `UNSIGNED_SHORT` or `UNSIGNED_INT` depending on the number of vertices
+    pub fn index_type(&self) -> u32 {
+        self.index_type
+    }
+
+    pub fn index_size(&self) -> i32 {
+        if self.index_type == WebGl2RenderingContext::UNSIGNED_SHORT {
+            2
+        } else {
+            4
+        }
+    }
+
+    pub fn index_count(&self) -> i32 {
+        self.indices.len() as i32
+    }
+
     pub fn to_f32_vec(&self) -> Vec<f32> {
         Self::make_buffer(&self.vertices)
     }
 
     fn make_buffer(vertices: &[VertexData]) -> Vec<f32> {
-        let mut vec_f32 = Vec::with_capacity(vertices.len() * (3 + 3 + 2 + 4 + 4));
+        let mut vec_f32 = Vec::with_capacity(vertices.len() * (3 + 3 + 2 + 4 + 4 + 3));
         for vertex in vertices.iter() {
             Self::push_vector3(&mut vec_f32, vertex.point);
             Self::push_vector3(&mut vec_f32, vertex.normal);
             Self::push_vector2(&mut vec_f32, vertex.texture_coord);
             Self::push_vector4(&mut vec_f32, vertex.bones);
             Self::push_vector4(&mut vec_f32, vertex.weights);
+            Self::push_vector3(&mut vec_f32, vertex.color);
         }
         vec_f32
     }
 
+    fn make_bounds(vertices: &[VertexData]) -> (Vector3, Vector3) {
+        if vertices.is_empty() {
+            return (Vector3::zero(), Vector3::zero());
+        }
+        let mut min = vertices[0].point;
+        let mut max = vertices[0].point;
+        for vertex in vertices.iter() {
+            for i in 0..3 {
+                min.set(i, min.get(i).min(vertex.point.get(i)));
+                max.set(i, max.get(i).max(vertex.point.get(i)));
+            }
+        }
+        (min, max)
+    }
+
     fn push_vector3(vec_f32: &mut Vec<f32>, vector: Vector3) {
         vec_f32.push(vector.x());
         vec_f32.push(vector.y());
@@ -93,7 +281,83 @@ impl Shape {
         3 + 3 + 2 + 4
     }
 
-    pub fn step(&self) -> i32 {
+    pub fn color_offset(&self) -> i32 {
         3 + 3 + 2 + 4 + 4
     }
+
+    pub fn step(&self) -> i32 {
+        3 + 3 + 2 + 4 + 4 + 3
+    }
+}
+
+pub struct Shapes {
+    pub shapes: Vec<Rc<Shape>>,
+    pub names: HashMap<String, usize>,
+}
+
+impl Shapes {
+    pub fn from_meshes(meshes: ObjMeshes, gl: &WebGl2RenderingContext) -> Self {
+        Self {
+            shapes: meshes
+                .meshes
+                .into_iter()
+                .map(|mesh| Rc::new(Shape::from_mesh(mesh, gl)))
+                .collect(),
+            names: meshes.names,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Rc<Shape>> {
+        self.names.get(name).map(|idx| &self.shapes[*idx])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Rc<Shape>)> {
+        self.names
+            .iter()
+            .map(|(name, idx)| (name.as_str(), &self.shapes[*idx]))
+    }
+}
+
+impl Index<&str> for Shapes {
+    type Output = Rc<Shape>;
+
+    fn index(&self, name: &str) -> &Self::Output {
+        &self.shapes[self.names[name]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_interpolate_texture_coords() {
+        // Unit quad in the xy plane, texture coordinates follow x and y
+        let vertices: Vec<VertexData> = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+            .into_iter()
+            .map(|(x, y)| VertexData {
+                point: Vector3::from_xyz(x, y, 0.0),
+                normal: Vector3::from_xyz(0.0, 0.0, 1.0),
+                texture_coord: Vector2::from_xy(x, y),
+                bones: Vector4::zero(),
+                weights: Vector4::zero(),
+                color: VertexData::default_color(),
+            })
+            .collect();
+        let indices = [0, 1, 2, 0, 2, 3];
+        let positions: Vec<Vector3> = vertices.iter().map(|it| it.point).collect();
+        let bvh = TriangleBvh::new(&positions, &indices);
+
+        for (x, y, triangle) in [(0.75, 0.25, 0), (0.2, 0.6, 1), (0.5, 0.125, 0)] {
+            let ray = Ray::new(
+                Vector3::from_xyz(x, y, 2.0),
+                Vector3::from_xyz(0.0, 0.0, -1.0),
+            );
+            let hit = bvh.cast_ray(&ray).unwrap();
+            assert_eq!(hit.triangle, triangle);
+            let texture_coord = Shape::texture_coord(&vertices, &indices, &hit);
+            assert!((texture_coord.x() - x).abs() < 1e-6, "{:?}", texture_coord);
+            assert!((texture_coord.y() - y).abs() < 1e-6, "{:?}", texture_coord);
+        }
+    }
 }
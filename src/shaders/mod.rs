@@ -1,4 +1,4 @@
-use js_sys::Float32Array;
+use js_sys::{Float32Array, Uint16Array, Uint32Array};
 use wasm_bindgen::prelude::wasm_bindgen;
 use web_sys::{
     HtmlCanvasElement, WebGl2RenderingContext as Gl, WebGlBuffer, WebGlProgram, WebGlShader,
@@ -10,6 +10,9 @@ pub mod render_light;
 pub mod view;
 pub mod wire_light;
 
+/// Size of the `bones` uniform array, slot 0 holds the identity for unskinned vertices.
+pub const MAX_BONES: usize = 100;
+
 pub fn make_f32_buffer(gl: &Gl, array: &[f32]) -> WebGlBuffer {
     let buffer = gl.create_buffer().unwrap();
 
@@ -26,6 +29,38 @@ pub fn make_f32_buffer(gl: &Gl, array: &[f32]) -> WebGlBuffer {
     buffer
 }
 
+pub fn make_u16_index_buffer(gl: &Gl, array: &[u16]) -> WebGlBuffer {
+    let buffer = gl.create_buffer().unwrap();
+
+    gl.bind_buffer(Gl::ELEMENT_ARRAY_BUFFER, Some(&buffer));
+
+    gl.buffer_data_with_array_buffer_view(
+        Gl::ELEMENT_ARRAY_BUFFER,
+        &Uint16Array::from(array),
+        Gl::STATIC_DRAW,
+    );
+
+    gl.bind_buffer(Gl::ELEMENT_ARRAY_BUFFER, None);
+
+    buffer
+}
+
+pub fn make_u32_index_buffer(gl: &Gl, array: &[u32]) -> WebGlBuffer {
+    let buffer = gl.create_buffer().unwrap();
+
+    gl.bind_buffer(Gl::ELEMENT_ARRAY_BUFFER, Some(&buffer));
+
+    gl.buffer_data_with_array_buffer_view(
+        Gl::ELEMENT_ARRAY_BUFFER,
+        &Uint32Array::from(array),
+        Gl::STATIC_DRAW,
+    );
+
+    gl.bind_buffer(Gl::ELEMENT_ARRAY_BUFFER, None);
+
+    buffer
+}
+
 pub fn init_shader_program(gl: &Gl, vs_source: &str, fs_source: &str) -> WebGlProgram {
     let vs = load_shader(gl, Gl::VERTEX_SHADER, vs_source);
     let fs = load_shader(gl, Gl::FRAGMENT_SHADER, fs_source);
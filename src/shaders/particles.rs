@@ -10,10 +10,10 @@ pub struct ParticlesShader {
     height: i32,
 
     projection_location: WebGlUniformLocation,
-    verts_in_particle_location: WebGlUniformLocation,
     verts_location: WebGlUniformLocation,
     texture_location: WebGlUniformLocation,
     verts_stride_location: WebGlUniformLocation,
+    vertex_size_location: WebGlUniformLocation,
 
     transforms_locations: Vec<WebGlUniformLocation>,
 }
@@ -28,12 +28,10 @@ impl ParticlesShader {
         let program = init_shader_program(gl, VS_SOURCE, FS_SOURCE);
 
         let projection_location = gl.get_uniform_location(&program, "projection").unwrap();
-        let verts_in_particle_location = gl
-            .get_uniform_location(&program, "vertsInParticle")
-            .unwrap();
         let verts_location = gl.get_uniform_location(&program, "verts").unwrap();
         let texture_location = gl.get_uniform_location(&program, "image").unwrap();
         let verts_stride_location = gl.get_uniform_location(&program, "vertsStride").unwrap();
+        let vertex_size_location = gl.get_uniform_location(&program, "vertexSize").unwrap();
 
         let transforms_locations = (0..MAX_PARTICLES)
             .map(|i| {
@@ -48,10 +46,10 @@ impl ParticlesShader {
             height,
 
             projection_location,
-            verts_in_particle_location,
             texture_location,
             verts_location,
             verts_stride_location,
+            vertex_size_location,
 
             transforms_locations,
         }
@@ -66,7 +64,10 @@ impl ParticlesShader {
         gl.use_program(Some(&self.program));
         gl.viewport(0, 0, self.width, self.height);
 
-        gl.bind_buffer(Gl::ARRAY_BUFFER, Some(&particles.shape.get_buffer()));
+        gl.bind_buffer(
+            Gl::ELEMENT_ARRAY_BUFFER,
+            Some(&particles.shape.get_index_buffer()),
+        );
 
         gl.uniform_matrix4fv_with_f32_array(
             Some(&self.projection_location),
@@ -74,8 +75,8 @@ impl ParticlesShader {
             &(camera.matrix() * particles.transform.matrix()),
         );
 
-        gl.uniform1i(Some(&self.verts_in_particle_location), particles.vertices());
         gl.uniform1i(Some(&self.verts_stride_location), particles.stride());
+        gl.uniform1i(Some(&self.vertex_size_location), particles.shape.step());
 
         gl.active_texture(Gl::TEXTURE0);
         gl.bind_texture(Gl::TEXTURE_2D, Some(particles.texture.location()));
@@ -93,10 +94,12 @@ impl ParticlesShader {
             );
         }
 
-        gl.draw_arrays(
+        gl.draw_elements_instanced_with_i32(
             Gl::TRIANGLES,
+            particles.shape.index_count(),
+            particles.shape.index_type(),
             0,
-            particles.particles.len() as i32 * particles.vertices(),
+            particles.particles.len() as i32,
         );
     }
 }
@@ -1,6 +1,6 @@
 use web_sys::{WebGl2RenderingContext as Gl, WebGlProgram, WebGlUniformLocation};
 
-use super::init_shader_program;
+use super::{init_shader_program, MAX_BONES};
 use crate::{geometry::Matrix, objects::object::Object};
 
 pub struct RenderLight {
@@ -22,8 +22,6 @@ pub struct RenderLight {
 const VS_SOURCE: &str = include_str!("src/render_light.vert");
 const FS_SOURCE: &str = include_str!("src/render_light.frag");
 
-const MAX_BONES: usize = 100;
-
 impl RenderLight {
     pub fn new(gl: &Gl, width: i32, height: i32) -> Self {
         let program = init_shader_program(gl, VS_SOURCE, FS_SOURCE);
@@ -66,6 +64,10 @@ impl RenderLight {
         gl.use_program(Some(&self.program));
 
         gl.bind_buffer(Gl::ARRAY_BUFFER, Some(&obj.shape.get_buffer()));
+        gl.bind_buffer(
+            Gl::ELEMENT_ARRAY_BUFFER,
+            Some(&obj.shape.get_index_buffer()),
+        );
 
         gl.vertex_attrib_pointer_with_i32(
             self.vertex_position_location,
@@ -116,6 +118,11 @@ impl RenderLight {
             }
         }
 
-        gl.draw_arrays(Gl::TRIANGLES, 0, obj.shape.buffer_length());
+        gl.draw_elements_with_i32(
+            Gl::TRIANGLES,
+            obj.shape.index_count(),
+            obj.shape.index_type(),
+            0,
+        );
     }
 }
@@ -1,7 +1,6 @@
 #version 300 es
 
 #define MAX_PARTICLES 200
-#define STRIDE (3 + 3 + 2 + 4 + 4)
 
 in vec3 vertexPosition;
 
@@ -11,21 +10,20 @@ uniform mat4 projection;
 
 uniform mat4[MAX_PARTICLES] transforms;
 
-uniform int vertsInParticle;
-
 uniform sampler2D verts;
 uniform int vertsStride;
+// Texels of one vertex, `Shape::step`
+uniform int vertexSize;
 
 ivec2 vertexIdxToTexel(int idx) {
-    return ivec2((idx % vertsStride) * STRIDE, idx / vertsStride);
+    return ivec2((idx % vertsStride) * vertexSize, idx / vertsStride);
 }
 
 void main() {
-    int particleIdx = gl_VertexID / vertsInParticle;
-    int vertexIdx = gl_VertexID % vertsInParticle;
-    ivec2 vertexTexel = vertexIdxToTexel(vertexIdx);
+    // Drawn instanced by the shape indices, so gl_VertexID is the index of a unique vertex
+    ivec2 vertexTexel = vertexIdxToTexel(gl_VertexID);
 
-    gl_Position = projection * transforms[particleIdx] * vec4(
+    gl_Position = projection * transforms[gl_InstanceID] * vec4(
         texelFetch(verts, vertexTexel + ivec2(0, 0), 0).r,
         texelFetch(verts, vertexTexel + ivec2(1, 0), 0).r,
         texelFetch(verts, vertexTexel + ivec2(2, 0), 0).r,
@@ -8,6 +8,7 @@ precision mediump float;
 in vec2 textCoord;
 in vec4 fragNormal;
 in vec4 fragPosition;
+in vec3 fragColor;
 
 out vec4 color;
 
@@ -37,6 +38,11 @@ uniform int ignoreLight;
 
 uniform sampler2D textureMap;
 
+uniform vec3 materialDiffuse;
+uniform float materialSpecular;
+uniform float materialShininess;
+uniform float materialAlpha;
+
 uniform vec3 cameraPosition;
 
 float ambient = 0.2;
@@ -47,8 +53,8 @@ float calculatePhong(float lightDiffuse, float lightSpecular, vec3 toLightVec, v
 
     vec3 reflection = dot(toLightVec, normal) * normal * 2.0 - toLightVec;
     vec3 toCameraVec = normalize(cameraPosition - fragPosition.xyz);
-    float spec = pow(max(dot(reflection, toCameraVec), 0.0), 100.0);
-    float newSpecular = lightSpecular * spec;
+    float spec = pow(max(dot(reflection, toCameraVec), 0.0), materialShininess);
+    float newSpecular = lightSpecular * materialSpecular * spec;
 
     return newDiffuse + newSpecular;
 }
@@ -127,8 +133,8 @@ void main() {
     float dist = clamp((distance(fragPosition.xyz, cameraPosition) - 5.0) / 30.0, 0.0, 1.0);
     color = mix(
         vec4(
-            texture(textureMap, textCoord).rgb * brightness,
-            1.0
+            texture(textureMap, textCoord).rgb * fragColor * materialDiffuse * brightness,
+            materialAlpha
         ),
         vec4(1.0),
         dist
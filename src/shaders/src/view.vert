@@ -7,10 +7,12 @@ in vec3 vertexPosition;
 in vec3 vertexNormal;
 in vec4 vertexBones;
 in vec4 vertexWeights;
+in vec3 vertexColor;
 
 out vec2 textCoord;
 out vec4 fragNormal;
 out vec4 fragPosition;
+out vec3 fragColor;
 
 uniform mat4 position;
 uniform mat4 normalMat;
@@ -36,4 +38,5 @@ void main() {
     fragNormal = normalMat * vec4(vertexNormal, 1.0);
     textCoord = vertexTexture;
     fragPosition = position * pos;
+    fragColor = vertexColor;
 }
@@ -1,11 +1,11 @@
 use web_sys::{WebGl2RenderingContext as Gl, WebGlProgram, WebGlUniformLocation};
 
-use super::init_shader_program;
+use super::{init_shader_program, MAX_BONES};
 use crate::{
     camera::Camera,
     geometry::Matrix,
     light::{Directional, Light, Point},
-    objects::object::Object,
+    objects::{object::Object, parsers::material::Material},
 };
 
 pub struct ViewShader {
@@ -19,12 +19,14 @@ pub struct ViewShader {
     vertex_textcoord_location: u32,
     vertex_bones_location: u32,
     vertex_weights_location: u32,
+    vertex_color_location: u32,
 
     camera_location: WebGlUniformLocation,
     camera_pos_location: WebGlUniformLocation,
     position_location: WebGlUniformLocation,
     normal_mat_location: WebGlUniformLocation,
     texture_location: WebGlUniformLocation,
+    material: MaterialUniform,
     ignore_light_location: WebGlUniformLocation,
     light: Vec<LightUniform>,
     bones_locations: Vec<WebGlUniformLocation>,
@@ -35,7 +37,6 @@ const FS_SOURCE: &str = include_str!("src/view.frag");
 const VS_SOURCE: &str = include_str!("src/view.vert");
 
 const MAX_LIGHTS: usize = 16;
-const MAX_BONES: usize = 100;
 
 impl ViewShader {
     pub fn new(gl: &Gl, width: i32, height: i32) -> Self {
@@ -46,12 +47,14 @@ impl ViewShader {
         let vertex_textcoord_location = gl.get_attrib_location(&program, "vertexTexture") as u32;
         let vertex_bones_location = gl.get_attrib_location(&program, "vertexBones") as u32;
         let vertex_weights_location = gl.get_attrib_location(&program, "vertexWeights") as u32;
+        let vertex_color_location = gl.get_attrib_location(&program, "vertexColor") as u32;
 
         let camera_location = gl.get_uniform_location(&program, "camera").unwrap();
         let camera_pos_location = gl.get_uniform_location(&program, "cameraPosition").unwrap();
         let position_location = gl.get_uniform_location(&program, "position").unwrap();
         let normal_mat_location = gl.get_uniform_location(&program, "normalMat").unwrap();
         let texture_location = gl.get_uniform_location(&program, "textureMap").unwrap();
+        let material = MaterialUniform::new(gl, &program);
 
         let bones_count_location = gl.get_uniform_location(&program, "boneCount").unwrap();
         let mut bones_locations = vec![];
@@ -78,12 +81,14 @@ impl ViewShader {
             vertex_textcoord_location,
             vertex_bones_location,
             vertex_weights_location,
+            vertex_color_location,
 
             camera_location,
             camera_pos_location,
             position_location,
             normal_mat_location,
             texture_location,
+            material,
 
             bones_locations,
             bones_count_location,
@@ -104,6 +109,10 @@ impl ViewShader {
         gl.viewport(0, 0, self.width, self.height);
 
         gl.bind_buffer(Gl::ARRAY_BUFFER, Some(&obj.shape.get_buffer()));
+        gl.bind_buffer(
+            Gl::ELEMENT_ARRAY_BUFFER,
+            Some(&obj.shape.get_index_buffer()),
+        );
 
         gl.vertex_attrib_pointer_with_i32(
             self.vertex_position_location,
@@ -155,6 +164,16 @@ impl ViewShader {
         );
         gl.enable_vertex_attrib_array(self.vertex_weights_location);
 
+        gl.vertex_attrib_pointer_with_i32(
+            self.vertex_color_location,
+            3,
+            Gl::FLOAT,
+            false,
+            obj.shape.step() * 4,
+            obj.shape.color_offset() * 4,
+        );
+        gl.enable_vertex_attrib_array(self.vertex_color_location);
+
         gl.uniform_matrix4fv_with_f32_array(Some(&self.camera_location), true, &camera.matrix());
         gl.uniform3fv_with_f32_array(Some(&self.camera_pos_location), &camera.position());
 
@@ -199,17 +218,62 @@ impl ViewShader {
         }
 
         gl.active_texture(Gl::TEXTURE0);
-        gl.bind_texture(Gl::TEXTURE_2D, Some(obj.texture.location()));
         gl.uniform1i(Some(&self.texture_location), 0);
 
         gl.enable(Gl::BLEND);
         gl.blend_func(Gl::SRC_ALPHA, Gl::ONE_MINUS_SRC_ALPHA);
 
-        gl.draw_arrays(Gl::TRIANGLES, 0, obj.shape.buffer_length());
+        let default_material = Material::new("");
+        for submesh in obj.shape.submeshes() {
+            let material = obj.material(submesh.material.as_deref());
+
+            let texture = material
+                .and_then(|it| it.texture.as_ref())
+                .unwrap_or(&obj.texture);
+            gl.bind_texture(Gl::TEXTURE_2D, Some(texture.location()));
+
+            self.material.bind(
+                gl,
+                material.map(|it| &it.material).unwrap_or(&default_material),
+            );
+
+            gl.draw_elements_with_i32(
+                Gl::TRIANGLES,
+                submesh.count as i32,
+                obj.shape.index_type(),
+                submesh.start as i32 * obj.shape.index_size(),
+            );
+        }
         gl.disable(Gl::BLEND);
     }
 }
 
+struct MaterialUniform {
+    diffuse: WebGlUniformLocation,
+    specular: WebGlUniformLocation,
+    shininess: WebGlUniformLocation,
+    alpha: WebGlUniformLocation,
+}
+
+impl MaterialUniform {
+    fn new(gl: &Gl, program: &WebGlProgram) -> Self {
+        let uniform = |name: &str| gl.get_uniform_location(program, name).unwrap();
+        Self {
+            diffuse: uniform("materialDiffuse"),
+            specular: uniform("materialSpecular"),
+            shininess: uniform("materialShininess"),
+            alpha: uniform("materialAlpha"),
+        }
+    }
+
+    fn bind(&self, gl: &Gl, material: &Material) {
+        gl.uniform3fv_with_f32_array(Some(&self.diffuse), &material.diffuse);
+        gl.uniform1f(Some(&self.specular), material.specular_intensity());
+        gl.uniform1f(Some(&self.shininess), material.shininess);
+        gl.uniform1f(Some(&self.alpha), material.dissolve);
+    }
+}
+
 struct LightUniform {
     typ: WebGlUniformLocation,
     diffuse: WebGlUniformLocation,
@@ -1,23 +1,38 @@
-use std::rc::Rc;
+use std::{cell::Cell, rc::Rc};
+
+use web_sys::WebGl2RenderingContext as Gl;
 
 use crate::{
     camera::Camera,
     controls::{ControlKey, Controls},
     download::{ResourceManager, ResourceRequest},
     entity_manager::entity_factory::EntityFactory,
-    geometry::{raycast::Ray, Transform, Vector3},
+    geometry::{broadphase::SweepAndPrune, frustum::Frustum, raycast::Ray, Transform, Vector3},
     gl_context::GlContext,
     light::Light,
     objects::{
+        material::ObjectMaterial,
         object::Object,
-        parsers::{animation::Animation, skeleton::Skeleton, skinning::Skinning},
+        parsers::{
+            animation::Animation, material::MaterialLibrary, skeleton::Skeleton, skinning::Skinning,
+        },
         particles::Particles,
         prefab::{ObjectPrefab, Prefab},
         rigid_body::RigidBody,
-        shape::Shape,
+        shape::{Shape, ShapeHit},
     },
 };
 
+/// Objects drawn and skipped by frustum culling during the last `World::draw`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CullingStats {
+    pub drawn: usize,
+    pub culled: usize,
+    /// Counted once per light
+    pub shadow_drawn: usize,
+    pub shadow_culled: usize,
+}
+
 pub struct World {
     rm: ResourceManager,
 
@@ -25,6 +40,7 @@ pub struct World {
     objects: Vec<Object>,
     particles: Vec<Particles>,
     bodies: Vec<RigidBody>,
+    broadphase: SweepAndPrune,
 
     picked_object: isize,
 
@@ -32,6 +48,7 @@ pub struct World {
     pub camera: Camera,
 
     draw_gizmos: bool,
+    culling_stats: Cell<CullingStats>,
 }
 
 impl World {
@@ -43,12 +60,14 @@ impl World {
             objects: vec![],
             particles: vec![],
             bodies: vec![],
+            broadphase: SweepAndPrune::new(),
 
             picked_object: -1,
 
             lights: vec![],
             camera,
             draw_gizmos: false,
+            culling_stats: Cell::new(CullingStats::default()),
         }
     }
 
@@ -67,6 +86,7 @@ impl World {
             RR::text("resources/person.skin", "Person.skin"),
             RR::text("resources/person.anim", "Person.anim"),
             RR::text("resources/person.obj", "Person"),
+            RR::text("resources/person.mtl", "Person.mtl"),
             RR::image("resources/skull.jpg", "Skull"),
             RR::image("resources/crate_1.jpg", "Grass"),
             RR::image("resources/carpet.jpg", "Carpet"),
@@ -114,13 +134,15 @@ impl World {
         // let player = &self.bodies[0].transform;
         // player.translate(0.0, -_delta_time * 2.0, 0.0);
 
-        for i in 1..self.bodies.len() {
-            for j in (i + 1)..self.bodies.len() {
-                self.bodies[i].collide(&self.bodies[j]);
-            }
-        }
-        for j in 1..self.bodies.len() {
-            self.bodies[0].collide(&self.bodies[j]);
+        self.broadphase
+            .update(self.bodies.iter().map(RigidBody::bounds).collect());
+        // Pairs come from the boxes before anything is pushed out, bodies that only
+        // start to overlap while these pairs are resolved are separated next tick
+        let mut pairs = self.broadphase.pairs();
+        // The player is pushed out last, after the other bodies settled
+        pairs.sort_by_key(|&(i, j)| (i == 0, i, j));
+        for (i, j) in pairs {
+            self.bodies[i].collide(&self.bodies[j]);
         }
     }
 
@@ -142,6 +164,7 @@ impl World {
     }
 
     pub fn draw(&self, context: &GlContext) {
+        let mut stats = CullingStats::default();
         for light in self.lights.iter() {
             if self.draw_gizmos {
                 if let Light::Directional(d) = light {
@@ -162,17 +185,30 @@ impl World {
             }
             context.bind_framebuffer(light);
             context.clear();
+            let frustums = light.frustums();
             for obj in self.objects.iter() {
                 if obj.ignored_by_light {
                     continue;
                 }
+                if !Self::is_visible(obj, &frustums) {
+                    stats.shadow_culled += 1;
+                    continue;
+                }
+                stats.shadow_drawn += 1;
                 context.render_light(obj, light);
             }
             context.unbind_framebuffer();
         }
+        let frustum = [Frustum::from_matrix(&self.camera.matrix())];
         for obj in self.objects.iter() {
+            if !Self::is_visible(obj, &frustum) {
+                stats.culled += 1;
+                continue;
+            }
+            stats.drawn += 1;
             context.view(obj, &self.camera, &self.lights);
         }
+        self.culling_stats.set(stats);
         for part in self.particles.iter() {
             context.particles(part, &self.camera);
         }
@@ -187,6 +223,27 @@ impl World {
         }
     }
 
+    pub fn culling_stats(&self) -> CullingStats {
+        self.culling_stats.get()
+    }
+
+    /// Closest object in the middle of the view and where it is hit. Not done every tick,
+    /// animated objects are skinned and get a new triangle hierarchy for each cast.
+    pub fn look_at_object(&self) -> Option<(usize, ShapeHit)> {
+        let ray = Ray::new(self.camera.position(), self.camera.direction().normalized());
+        self.objects
+            .iter()
+            .enumerate()
+            .filter_map(|(i, obj)| obj.cast_ray(&ray).map(|hit| (i, hit)))
+            .min_by(|(_, a), (_, b)| a.distance.total_cmp(&b.distance))
+    }
+
+    /// Objects without bounds are always drawn.
+    fn is_visible(obj: &Object, frustums: &[Frustum]) -> bool {
+        obj.bounds()
+            .is_none_or(|(min, max)| frustums.iter().any(|it| it.intersects_box(min, max)))
+    }
+
     pub fn add_object(&mut self, object: Object) {
         self.objects.push(object);
     }
@@ -195,33 +252,51 @@ impl World {
         self.bodies.push(body);
     }
 
+    /// Shape, skeleton and animation of an animated model, `None` if a file fails to parse.
+    fn load_animated(
+        &self,
+        name: &str,
+        gl: &Gl,
+    ) -> Option<(Rc<Shape>, Rc<Skeleton>, Rc<Animation>)> {
+        let skl = Rc::new(
+            self.rm
+                .parse_text(&format!("{}.skl", name), Skeleton::try_from_file)?,
+        );
+        let skin = self
+            .rm
+            .parse_text(&format!("{}.skin", name), Skinning::try_parse)?;
+        let anim = self.rm.parse_text(&format!("{}.anim", name), |it| {
+            Animation::try_parse(it, &skl)
+        })?;
+        let shape = self
+            .rm
+            .parse_text(name, |it| Shape::try_parse_with_skin(it, &skin, gl))?;
+        Some((Rc::new(shape), skl, Rc::new(anim)))
+    }
+
+    /// Assets that fail to parse are logged and left out of the scene.
     pub fn init_0(&mut self, context: &GlContext) {
         let gl = context.gl();
-        let skull = Rc::new(Shape::parse(&self.rm.get_text("Skull"), &gl));
-        let cube = Rc::new(Shape::parse(&self.rm.get_text("Cube"), &gl));
-        let floor = Rc::new(Shape::parse(&self.rm.get_text("Floor"), &gl));
-        let gleb = Rc::new(Shape::parse(&self.rm.get_text("Gleb"), &gl));
-
-        let table_skl = Rc::new(Skeleton::from_file(&self.rm.get_text("Walk.skl")));
-        let table_skin = Rc::new(Skinning::parse(&self.rm.get_text("Walk.skin")));
-        let table_anim = Rc::new(Animation::parse(&self.rm.get_text("Walk.anim"), &table_skl));
-        let table = Rc::new(Shape::parse_with_skin(
-            &self.rm.get_text("Walk"),
-            &table_skin,
-            &gl,
-        ));
-
-        let person_skl = Rc::new(Skeleton::from_file(&self.rm.get_text("Person.skl")));
-        let person_skin = Rc::new(Skinning::parse(&self.rm.get_text("Person.skin")));
-        let person_anim = Rc::new(Animation::parse(
-            &self.rm.get_text("Person.anim"),
-            &person_skl,
-        ));
-        let person = Rc::new(Shape::parse_with_skin(
-            &self.rm.get_text("Person"),
-            &person_skin,
-            &gl,
-        ));
+        let shape = |name: &str| {
+            self.rm
+                .parse_text(name, |it| Shape::try_parse(it, &gl))
+                .map(Rc::new)
+        };
+        let skull = shape("Skull");
+        let cube = shape("Cube");
+        let floor = shape("Floor");
+        let gleb = shape("Gleb");
+
+        let table = self.load_animated("Walk", &gl);
+        let person = self.load_animated("Person", &gl);
+
+        let person_materials = self
+            .rm
+            .parse_text("Person.mtl", MaterialLibrary::try_parse)
+            .map(|library| {
+                ObjectMaterial::from_library(&library, |name| self.rm.try_get_texture(name))
+            })
+            .unwrap_or_default();
 
         let grass_texture = self.rm.get_texture("Grass");
         let skull_texture = self.rm.get_texture("Skull");
@@ -229,13 +304,17 @@ impl World {
 
         // Prefabs
 
-        let cube = ObjectPrefab::new(&cube, &grass_texture).with_body(
-            Vector3::from_xyz(2.0, 2.0, 2.0),
-            Vector3::zero(),
-            true,
-        );
+        let cube = cube.map(|cube| {
+            ObjectPrefab::new(&cube, &grass_texture).with_body(
+                Vector3::from_xyz(2.0, 2.0, 2.0),
+                Vector3::zero(),
+                true,
+            )
+        });
 
-        let table = ObjectPrefab::new(&table, &grass_texture).with_animation(&table_anim);
+        let table = table.map(|(table, _, table_anim)| {
+            ObjectPrefab::new(&table, &grass_texture).with_animation(&table_anim)
+        });
 
         // Rigid bodies
 
@@ -245,6 +324,7 @@ impl World {
             Vector3::from_xyz(0.0, -1.0, 0.0),
             player_transform.clone(),
         )
+        .as_capsule()
         .as_movable();
         self.camera.transform.set_parent(player_transform);
         self.bodies.push(body);
@@ -258,95 +338,117 @@ impl World {
 
         // Particles
 
-        let particles = Particles::new(&gl, skull.clone(), skull_texture.clone());
-        particles.transform.translate(15.0, 0.0, 0.0);
-        self.particles.push(particles);
+        if let Some(skull) = &skull {
+            let particles = Particles::new(&gl, skull.clone(), skull_texture.clone());
+            particles.transform.translate(15.0, 0.0, 0.0);
+            self.particles.push(particles);
+        }
 
         // Objects
 
-        for i in 0..10 {
-            table.construct(
-                {
-                    let t = Transform::from_xyz_hv(i as f32 - 6.0, -2.0, 0.0, 0.0, 0.0);
-                    t.rotate_h(-1.57);
-                    t
-                },
-                self,
-            );
+        if let Some(table) = &table {
+            for i in 0..10 {
+                table.construct(
+                    {
+                        let t = Transform::from_xyz_hv(i as f32 - 6.0, -2.0, 0.0, 0.0, 0.0);
+                        t.rotate_h(-1.57);
+                        t
+                    },
+                    self,
+                );
+            }
         }
 
         self.picked_object = self.objects.len() as isize;
 
-        let person_object = Object::new(
-            person,
-            grass_texture.clone(),
-            Transform::from_xyz(0.0, -2.0, -10.0),
-        )
-        .with_skeleton(&person_skl)
-        .with_animation(person_anim);
-
-        let bone_transform = person_object
-            .get_bone_transform(person_skl.names["upfinger3.L"])
-            .clone();
-
-        self.objects.push(person_object);
-
-        self.objects.push(Object::new(gleb, grass_texture, {
-            let t = Transform::from_xyz(-5.0, -2.0, -5.0);
-            t.scale(0.2);
-            t
-        }));
-
-        self.objects.push(Object::new(skull, skull_texture, {
-            let t = Transform::from_xyz(0.0, 0.3, 0.0);
-            t.rotate_v(1.2 * std::f32::consts::PI / 2.0);
-            t.scale(0.1);
-            t
-        }));
-
-        cube.construct(Transform::from_xyz(5.0, -1.0, 5.0), self);
-
-        let carpet_transform = {
-            let t = Transform::from_xyz(0.0, -2.0, 0.0);
-            t.scale(5.0);
-            t
-        };
-        self.objects.push(Object::new(
-            floor.clone(),
-            carpet_texture.clone(),
-            carpet_transform.clone(),
-        ));
+        let bone_transform = person.and_then(|(person, person_skl, person_anim)| {
+            let person_object = Object::new(
+                person,
+                grass_texture.clone(),
+                Transform::from_xyz(0.0, -2.0, -10.0),
+            )
+            .with_skeleton(&person_skl)
+            .with_animation(person_anim)
+            .with_materials(person_materials);
+
+            let bone_transform = match person_skl.names.get("upfinger3.L") {
+                Some(&bone) => Some(person_object.get_bone_transform(bone).clone()),
+                None => {
+                    log::error!("Person skeleton has no upfinger3.L bone to attach the light to");
+                    None
+                }
+            };
 
-        self.objects
-            .push(Object::new(floor.clone(), carpet_texture.clone(), {
-                let t = Transform::from_xyz(0.0, 0.0, -2.0);
-                t.set_parent(carpet_transform);
+            self.objects.push(person_object);
+            bone_transform
+        });
+
+        if let Some(gleb) = gleb {
+            self.objects.push(Object::new(gleb, grass_texture, {
+                let t = Transform::from_xyz(-5.0, -2.0, -5.0);
+                t.scale(0.2);
                 t
             }));
-        self.objects
-            .push(Object::new(floor.clone(), carpet_texture.clone(), {
-                let t = Transform::from_xyz(0.0, 4.0, 0.0);
-                t.scale(5.0);
-                t.rotate_v(std::f32::consts::PI);
+        }
+
+        if let Some(skull) = skull {
+            self.objects.push(Object::new(skull, skull_texture, {
+                let t = Transform::from_xyz(0.0, 0.3, 0.0);
+                t.rotate_v(1.2 * std::f32::consts::PI / 2.0);
+                t.scale(0.1);
                 t
             }));
+        }
 
-        self.objects
-            .push(Object::new(floor.clone(), carpet_texture.clone(), {
-                let t = Transform::from_xyz(0.0, 0.0, -15.0);
+        if let Some(cube) = &cube {
+            cube.construct(Transform::from_xyz(5.0, -1.0, 5.0), self);
+        }
+
+        if let Some(floor) = floor {
+            let carpet_transform = {
+                let t = Transform::from_xyz(0.0, -2.0, 0.0);
                 t.scale(5.0);
-                t.rotate_v(-std::f32::consts::PI / 2.0);
                 t
-            }));
+            };
+            self.objects.push(Object::new(
+                floor.clone(),
+                carpet_texture.clone(),
+                carpet_transform.clone(),
+            ));
+
+            self.objects
+                .push(Object::new(floor.clone(), carpet_texture.clone(), {
+                    let t = Transform::from_xyz(0.0, 0.0, -2.0);
+                    t.set_parent(carpet_transform);
+                    t
+                }));
+            self.objects
+                .push(Object::new(floor.clone(), carpet_texture.clone(), {
+                    let t = Transform::from_xyz(0.0, 4.0, 0.0);
+                    t.scale(5.0);
+                    t.rotate_v(std::f32::consts::PI);
+                    t
+                }));
 
-        self.objects.push(Object::new(floor, carpet_texture, {
-            let t = Transform::from_xyz(0.0, 4.0, -10.0);
-            t.scale(5.0);
-            t.rotate_v(std::f32::consts::PI);
-            t
-        }));
+            self.objects
+                .push(Object::new(floor.clone(), carpet_texture.clone(), {
+                    let t = Transform::from_xyz(0.0, 0.0, -15.0);
+                    t.scale(5.0);
+                    t.rotate_v(-std::f32::consts::PI / 2.0);
+                    t
+                }));
+
+            self.objects.push(Object::new(floor, carpet_texture, {
+                let t = Transform::from_xyz(0.0, 4.0, -10.0);
+                t.scale(5.0);
+                t.rotate_v(std::f32::consts::PI);
+                t
+            }));
+        }
 
-        cube.construct(Transform::from_xyz(0.0, -1.0, -5.0), self);
+        if let Some(cube) = &cube {
+            cube.construct(Transform::from_xyz(0.0, -1.0, -5.0), self);
+        }
 
         // === Lights ===
 
@@ -368,12 +470,14 @@ impl World {
             .with_color(Vector3::from_xyz(0.8, 0.8, 0.3));
         self.lights.push(light);
 
-        let light = Light::new_directional(&gl, {
-            let t = Transform::from_xyz(0.0, 0.0, 0.0);
-            t.set_parent(bone_transform);
-            t
-        })
-        .with_color(Vector3::from_xyz(1.0, 0.0, 0.0));
-        self.lights.push(light);
+        if let Some(bone_transform) = bone_transform {
+            let light = Light::new_directional(&gl, {
+                let t = Transform::from_xyz(0.0, 0.0, 0.0);
+                t.set_parent(bone_transform);
+                t
+            })
+            .with_color(Vector3::from_xyz(1.0, 0.0, 0.0));
+            self.lights.push(light);
+        }
     }
 }